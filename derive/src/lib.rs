@@ -0,0 +1,243 @@
+//! Custom derives for resty.
+//!
+//! Currently provides `#[derive(Validate)]`, supporting following field rules:
+//! - `#[validate(length(min = 1, max = 10))]` - length of strings and vectors,
+//! - `#[validate(range(min = 0, max = 100))]` - numeric ranges,
+//! - `#[validate(regex = "^[a-z]+$")]` - regular expression match,
+//! - `#[validate(email)]` - e-mail address,
+//! - `#[validate(nested)]` - validates nested value (or every element of a `Vec`).
+//!
+//! `Option` fields are only validated if the value is present.
+
+#![recursion_limit = "128"]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate regex;
+#[macro_use]
+extern crate syn;
+
+use proc_macro2::TokenStream;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+
+/// Derives `resty::validation::Validate` implementation.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    match impl_validate(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum Rule {
+    Length { min: Option<syn::Expr>, max: Option<syn::Expr> },
+    Range { min: Option<syn::Expr>, max: Option<syn::Expr> },
+    Regex(syn::LitStr),
+    Email,
+    Nested,
+}
+
+struct Bound {
+    name: syn::Ident,
+    value: syn::Expr,
+}
+
+impl Parse for Bound {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Bound { name, value })
+    }
+}
+
+fn parse_bounds(rule: &syn::Ident, input: ParseStream) -> syn::Result<(Option<syn::Expr>, Option<syn::Expr>)> {
+    let content;
+    parenthesized!(content in input);
+    let bounds = Punctuated::<Bound, Token![,]>::parse_terminated(&content)?;
+    let mut min = None;
+    let mut max = None;
+    for bound in bounds {
+        match bound.name.to_string().as_str() {
+            "min" => min = Some(bound.value),
+            "max" => max = Some(bound.value),
+            other => return Err(syn::Error::new(
+                bound.name.span(),
+                format!("Unknown `{}` bound: `{}`. Expected `min` or `max`.", rule, other),
+            )),
+        }
+    }
+
+    if min.is_none() && max.is_none() {
+        return Err(syn::Error::new(rule.span(), format!("`{}` requires `min` or `max` bound.", rule)));
+    }
+
+    Ok((min, max))
+}
+
+impl Parse for Rule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: syn::Ident = input.parse()?;
+        match name.to_string().as_str() {
+            "length" => {
+                let (min, max) = parse_bounds(&name, input)?;
+                Ok(Rule::Length { min, max })
+            },
+            "range" => {
+                let (min, max) = parse_bounds(&name, input)?;
+                Ok(Rule::Range { min, max })
+            },
+            "regex" => {
+                input.parse::<Token![=]>()?;
+                let pattern: syn::LitStr = input.parse()?;
+                // Invalid patterns are reported at compile time rather than on the first request.
+                if let Err(err) = regex::Regex::new(&pattern.value()) {
+                    return Err(syn::Error::new(pattern.span(), format!("Invalid regular expression: {}", err)));
+                }
+                Ok(Rule::Regex(pattern))
+            },
+            "email" => Ok(Rule::Email),
+            "nested" => Ok(Rule::Nested),
+            other => Err(syn::Error::new(name.span(), format!("Unknown validation rule: `{}`.", other))),
+        }
+    }
+}
+
+/// Returns the type parameter if given type is `wrapper<T>`.
+fn inner_type<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    let path = match *ty {
+        syn::Type::Path(ref path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match segment.arguments {
+        syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => match args.args[0] {
+            syn::GenericArgument::Type(ref ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn as_option<T: quote::ToTokens>(value: &Option<syn::Expr>, ty: T) -> TokenStream {
+    match *value {
+        Some(ref value) => quote! { Some((#value) as #ty) },
+        None => quote! { None },
+    }
+}
+
+fn rule_check(rule: &Rule, path: &str, ty: &syn::Type) -> TokenStream {
+    let check = match *rule {
+        Rule::Length { ref min, ref max } => {
+            let min = as_option(min, quote!(usize));
+            let max = as_option(max, quote!(usize));
+            quote! { ::resty::validation::length(value, #min, #max) }
+        },
+        Rule::Range { ref min, ref max } => {
+            let min = as_option(min, quote!(f64));
+            let max = as_option(max, quote!(f64));
+            quote! { ::resty::validation::range(value, #min, #max) }
+        },
+        Rule::Regex(ref pattern) => quote! { ::resty::validation::regex(value, #pattern) },
+        Rule::Email => quote! { ::resty::validation::email(value) },
+        Rule::Nested if inner_type(ty, "Vec").is_some() => {
+            return quote! {
+                for (i, item) in value.iter().enumerate() {
+                    if let Err(nested) = ::resty::validation::Validate::validate(item) {
+                        errors.nested(&format!("{}[{}]", #path, i), nested);
+                    }
+                }
+            };
+        },
+        Rule::Nested => {
+            return quote! {
+                if let Err(nested) = ::resty::validation::Validate::validate(value) {
+                    errors.nested(#path, nested);
+                }
+            };
+        },
+    };
+
+    quote! {
+        if let Err(reason) = #check {
+            errors.add(#path, reason);
+        }
+    }
+}
+
+fn impl_validate(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match input.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(ref fields), .. }) => &fields.named,
+        _ => return Err(syn::Error::new_spanned(input, "Validate can only be derived for structs with named fields.")),
+    };
+
+    let mut checks = vec![];
+    for field in fields {
+        let mut rules = vec![];
+        for attr in &field.attrs {
+            if attr.path.is_ident("validate") {
+                rules.extend(attr.parse_args_with(Punctuated::<Rule, Token![,]>::parse_terminated)?);
+            }
+        }
+
+        if rules.is_empty() {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().expect("Only named fields are supported; qed");
+        let path = ident.to_string();
+        let (ty, optional) = match inner_type(&field.ty, "Option") {
+            Some(ty) => (ty, true),
+            None => (&field.ty, false),
+        };
+        let field_checks = rules.iter().map(|rule| rule_check(rule, &path, ty));
+
+        checks.push(if optional {
+            quote! {
+                if let Some(ref value) = self.#ident {
+                    #(#field_checks)*
+                }
+            }
+        } else {
+            quote! {
+                {
+                    let value = &self.#ident;
+                    #(#field_checks)*
+                }
+            }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::resty::validation::Validate for #name #ty_generics #where_clause {
+            fn validate(&self) -> ::std::result::Result<(), ::resty::validation::Errors> {
+                #[allow(unused_mut)]
+                let mut errors = ::resty::validation::Errors::new();
+                #(#checks)*
+                errors.into_result()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule;
+
+    #[test]
+    fn should_reject_invalid_regex() {
+        assert!(syn::parse_str::<Rule>(r#"regex = "^[a-z]+$""#).is_ok());
+        let error = syn::parse_str::<Rule>(r#"regex = "[a-z""#).err().expect("Invalid pattern is rejected.");
+        assert!(error.to_string().starts_with("Invalid regular expression"), "{}", error);
+    }
+}
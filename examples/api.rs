@@ -5,7 +5,8 @@ extern crate resty;
 extern crate serde_derive;
 
 use std::sync::RwLock;
-use futures::Future;
+use futures::{future, Future};
+use resty::extract::{Json, Path, State};
 
 fn main() {
     let mut v1 = resty::Router::new();
@@ -19,7 +20,7 @@ fn main() {
     let mut server = resty::Router::new();
     // Compose routers to form the API
     server.add("/v1", v1);
-    server.post("/test", |request| {
+    server.post("/test", |request: resty::Request<_>| {
         request.json().map(|mut product: Product| {
             product.id += 1;
             product
@@ -71,53 +72,66 @@ impl Products {
             Err(resty::Error::not_found(""))
         }
     }
+
+    pub fn rename(&self, id: usize, name: String) -> Result<Product, resty::Error> {
+        let mut products = self.products.write().unwrap();
+        if id < products.len() {
+            products[id].name = name;
+            Ok(products[id].clone())
+        } else {
+            Err(resty::Error::not_found(""))
+        }
+    }
 }
 
 // TODO [ToDr] Derive this implementation
 impl Into<resty::Router> for Products {
     fn into(self) -> resty::Router {
-        let self_ = ::std::sync::Arc::new(self);
         let mut router = resty::Router::with_config(
             resty::Config::new().handle_head(false).extra_headers({
                 let mut h = resty::Headers::new();
                 h.set_raw("X-Server", "resty");
                 h
             })
-        );
+        ).with_state(self);
 
         // no params
-        let a = self_.clone();
-        router.get("/", move |_request| {
-            a.list()
+        router.get("/", |request: resty::Request<_>| {
+            request.state::<Products>()?.list()
         });
 
         // dynamic params
-        let a = self_.clone();
-        router.get("/{id}", move |request| {
-            a.single(request.params().get("id")?)
+        router.get("/{id}", |request: resty::Request<_>| {
+            request.state::<Products>()?.single(request.params().get("id")?)
         });
 
         // static params
-        let a = self_.clone();
-        router.get(url!(/test/{id:usize}), move |request| {
-            a.single(request.params().id)
+        router.get(url!(/test/{id:usize}), |request: resty::Request<_>| {
+            request.state::<Products>()?.single(request.params().id)
         });
 
-        let a = self_.clone();
-        router.put(url!(/{id:usize}), move |request| {
-            let a = a.clone();
+        router.put(url!(/{id:usize}), |request: resty::Request<_>| {
             let id = request.params().id;
-            request.json().map_err(Into::into).and_then(move |product| {
-                a.update(id, product)
+            let products = request.state::<Products>();
+            future::result(products).and_then(move |a| {
+                request.json().map_err(Into::into).and_then(move |product| {
+                    a.update(id, product)
+                })
             })
         });
 
+        // typed extractors
+        router.patch("/{id}", |(Path(id), Json(name), State(a)): (Path<usize>, Json<String>, State<Products>)| {
+            a.rename(id, name)
+        });
+
         // post request
-        let a = self_.clone();
-        router.post("/", move |request| {
-            let a = a.clone();
-            request.json().map_err(Into::into).and_then(move |product| {
-                a.add(product)
+        router.post("/", |request: resty::Request<_>| {
+            let products = request.state::<Products>();
+            future::result(products).and_then(move |a| {
+                request.json().map_err(Into::into).and_then(move |product| {
+                    a.add(product)
+                })
             })
         });
 
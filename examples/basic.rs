@@ -13,11 +13,11 @@ struct Call {
 fn main() {
     let mut server = resty::Router::new();
 
-    server.get("/", |_| {
+    server.get("/", |_: resty::Request<_>| {
         Ok("Hello World!") as Result<_, resty::Error>
     });
 
-    server.post("/", |request| {
+    server.post("/", |request: resty::Request<_>| {
         // Deserialize payload
         request.json().map(|mut call: Call| {
             call.test += 1;
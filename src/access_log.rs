@@ -0,0 +1,194 @@
+//! Access logging.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json;
+
+/// Access log output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Common Log Format extended with matched route pattern and latency:
+    ///
+    /// `127.0.0.1 - - [10/Oct/2017:13:55:36 +0000] "GET /v1/products/5 HTTP/1.1" 200 27 "/v1/products/{id}" 0.125ms`
+    Common,
+    /// JSON object per line.
+    Json,
+}
+
+/// A single access log entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    /// Time (in seconds since UNIX epoch) when the request was received.
+    pub timestamp: u64,
+    /// Peer address.
+    pub peer: Option<SocketAddr>,
+    /// Request method.
+    pub method: String,
+    /// Request path (including query).
+    pub path: String,
+    /// HTTP version.
+    pub version: String,
+    /// Pattern of the route that handled the request (i.e. `/v1/products/{id}`).
+    pub route: Option<String>,
+    /// Response status code.
+    pub status: u16,
+    /// Response size (if known).
+    pub size: Option<u64>,
+    /// Time it took to prepare the response (in milliseconds).
+    pub latency_ms: f64,
+}
+
+/// Access log writing entries for every handled request to given sink.
+pub struct AccessLog {
+    format: Format,
+    sink: Mutex<Box<Write + Send>>,
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "AccessLog({:?})", self.format)
+    }
+}
+
+impl AccessLog {
+    /// Creates access log writing to given sink.
+    pub fn new<W: Write + Send + 'static>(format: Format, sink: W) -> Self {
+        AccessLog {
+            format,
+            sink: Mutex::new(Box::new(sink)),
+        }
+    }
+
+    /// Creates access log writing to standard error.
+    pub fn stderr(format: Format) -> Self {
+        Self::new(format, io::stderr())
+    }
+
+    /// Creates access log appending to file at given path.
+    pub fn file<P: AsRef<Path>>(format: Format, path: P) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(format, file))
+    }
+
+    /// Writes the entry to the sink.
+    pub fn log(&self, entry: &Entry) {
+        let line = match self.format {
+            Format::Common => format!(
+                "{} - - [{}] \"{} {} {}\" {} {} \"{}\" {:.3}ms\n",
+                entry.peer.map(|peer| peer.ip().to_string()).unwrap_or_else(|| "-".into()),
+                clf_date(entry.timestamp),
+                entry.method,
+                entry.path,
+                entry.version,
+                entry.status,
+                entry.size.map(|size| size.to_string()).unwrap_or_else(|| "-".into()),
+                entry.route.as_deref().unwrap_or("-"),
+                entry.latency_ms,
+            ),
+            Format::Json => {
+                let mut line = serde_json::to_string(entry).expect("The serialization is infallible; qed");
+                line.push('\n');
+                line
+            },
+        };
+
+        let mut sink = self.sink.lock().unwrap_or_else(|e| e.into_inner());
+        // Failing to write the log should not affect request processing.
+        let _ = sink.write_all(line.as_bytes()).and_then(|_| sink.flush());
+    }
+}
+
+/// Returns number of seconds since UNIX epoch.
+pub(crate) fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Formats the timestamp (UTC) as in Common Log Format.
+fn clf_date(timestamp: u64) -> String {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let days = (timestamp / 86_400) as i64;
+    let secs = timestamp % 86_400;
+    // Converts days since epoch to a civil date.
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day, MONTHS[(month - 1) as usize], year,
+        secs / 3_600, secs % 3_600 / 60, secs % 60,
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use super::{clf_date, AccessLog, Entry, Format};
+
+    /// In-memory log output shared with the tests.
+    #[derive(Clone, Default)]
+    pub struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Sink {
+        pub fn output(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            timestamp: 1_507_643_736,
+            peer: Some("127.0.0.1:8080".parse().unwrap()),
+            method: "GET".into(),
+            path: "/v1/products/5".into(),
+            version: "HTTP/1.1".into(),
+            route: Some("/v1/products/{id}".into()),
+            status: 200,
+            size: Some(27),
+            latency_ms: 0.125,
+        }
+    }
+
+    #[test]
+    fn should_format_clf_date() {
+        assert_eq!(clf_date(0), "01/Jan/1970:00:00:00 +0000");
+        assert_eq!(clf_date(951_782_400), "29/Feb/2000:00:00:00 +0000");
+        assert_eq!(clf_date(1_507_643_736), "10/Oct/2017:13:55:36 +0000");
+    }
+
+    #[test]
+    fn should_write_entries_in_given_format() {
+        let sink = Sink::default();
+        AccessLog::new(Format::Common, sink.clone()).log(&entry());
+        AccessLog::new(Format::Json, sink.clone()).log(&entry());
+
+        let output = sink.output();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], r#"127.0.0.1 - - [10/Oct/2017:13:55:36 +0000] "GET /v1/products/5 HTTP/1.1" 200 27 "/v1/products/{id}" 0.125ms"#);
+        assert_eq!(lines[1], r#"{"timestamp":1507643736,"peer":"127.0.0.1:8080","method":"GET","path":"/v1/products/5","version":"HTTP/1.1","route":"/v1/products/{id}","status":200,"size":27,"latency_ms":0.125}"#);
+    }
+}
@@ -0,0 +1,453 @@
+//! Request authentication.
+//!
+//! Authenticators are set for the whole router via `Config::auth`
+//! and store the verified principal in request extensions.
+//! Permissions of the principal are checked against the ones
+//! required by the route (see `Route::require` and `Config::require`).
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+use futures::{future, Future, IntoFuture};
+use hyper;
+use hyper::header::{Authorization, Basic, Bearer};
+use serde_urlencoded;
+
+use error::Error;
+use middleware::{Middleware, MiddlewareResult};
+use request::Request;
+
+/// Credentials extracted from the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// HTTP Basic credentials.
+    Basic {
+        /// User name.
+        username: String,
+        /// Password (if provided).
+        password: Option<String>,
+    },
+    /// Bearer token.
+    Bearer(String),
+    /// API key.
+    ApiKey(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scheme {
+    Basic,
+    Bearer,
+    ApiKeyHeader(String),
+    ApiKeyQuery(String),
+}
+
+/// Permissions (roles or scopes) granted to the authenticated principal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permissions(BTreeSet<String>);
+
+impl Permissions {
+    /// Creates a set of permissions.
+    pub fn new<I, S>(permissions: I) -> Self where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Permissions(permissions.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns true if given permission is granted.
+    pub fn contains(&self, permission: &str) -> bool {
+        self.0.contains(permission)
+    }
+
+    /// Returns the required permissions that are not granted.
+    pub fn missing<'a>(&self, required: &'a [String]) -> Vec<&'a str> {
+        required.iter().filter(|p| !self.contains(p)).map(|p| &**p).collect()
+    }
+}
+
+type PermissionsFn<P> = Fn(&P) -> Permissions + Send + Sync;
+type Verify<P> = Fn(Credentials) -> Box<Future<Item = Option<P>, Error = Error>> + Send + Sync;
+
+/// Authentication middleware.
+///
+/// Extracts the credentials, verifies them using provided function
+/// and inserts the resulting principal (`P`) into request extensions.
+/// Requests with missing or invalid credentials are rejected with `401`
+/// and `WWW-Authenticate` header.
+pub struct Authenticator<P> {
+    scheme: Scheme,
+    realm: String,
+    optional: bool,
+    verify: Arc<Verify<P>>,
+    permissions: Option<Arc<PermissionsFn<P>>>,
+}
+
+impl<P> fmt::Debug for Authenticator<P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Authenticator({:?}, realm: {})", self.scheme, self.realm)
+    }
+}
+
+impl<P: Send + Sync + 'static> Authenticator<P> {
+    /// HTTP Basic authentication.
+    /// The verification function gets the username and password.
+    pub fn basic<F, I>(realm: &str, verify: F) -> Self where
+        F: Fn(String, Option<String>) -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = Option<P>, Error = Error>,
+        I::Future: 'static,
+    {
+        Self::new(Scheme::Basic, realm, move |credentials| match credentials {
+            Credentials::Basic { username, password } => Box::new(verify(username, password).into_future()),
+            _ => Box::new(future::ok(None)),
+        })
+    }
+
+    /// Bearer token authentication.
+    pub fn bearer<F, I>(realm: &str, verify: F) -> Self where
+        F: Fn(String) -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = Option<P>, Error = Error>,
+        I::Future: 'static,
+    {
+        Self::new(Scheme::Bearer, realm, move |credentials| match credentials {
+            Credentials::Bearer(token) => Box::new(verify(token).into_future()),
+            _ => Box::new(future::ok(None)),
+        })
+    }
+
+    /// API key passed in given header (i.e. `X-Api-Key`).
+    pub fn api_key_header<F, I>(header: &str, verify: F) -> Self where
+        F: Fn(String) -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = Option<P>, Error = Error>,
+        I::Future: 'static,
+    {
+        Self::api_key(Scheme::ApiKeyHeader(header.into()), verify)
+    }
+
+    /// API key passed in given query parameter (i.e. `api_key`).
+    pub fn api_key_query<F, I>(param: &str, verify: F) -> Self where
+        F: Fn(String) -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = Option<P>, Error = Error>,
+        I::Future: 'static,
+    {
+        Self::api_key(Scheme::ApiKeyQuery(param.into()), verify)
+    }
+
+    fn api_key<F, I>(scheme: Scheme, verify: F) -> Self where
+        F: Fn(String) -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = Option<P>, Error = Error>,
+        I::Future: 'static,
+    {
+        Self::new(scheme, "api", move |credentials| match credentials {
+            Credentials::ApiKey(key) => Box::new(verify(key).into_future()),
+            _ => Box::new(future::ok(None)),
+        })
+    }
+
+    fn new<F>(scheme: Scheme, realm: &str, verify: F) -> Self where
+        F: Fn(Credentials) -> Box<Future<Item = Option<P>, Error = Error>> + Send + Sync + 'static,
+    {
+        Authenticator {
+            scheme,
+            realm: realm.into(),
+            optional: false,
+            verify: Arc::new(verify),
+            permissions: None,
+        }
+    }
+
+    /// Allow requests without credentials (no principal is inserted then).
+    /// Credentials of a different `Authorization` scheme are treated as missing.
+    /// Requests with invalid or malformed credentials are still rejected,
+    /// so are requests without credentials to routes requiring permissions.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Extracts permissions granted to the principal.
+    ///
+    /// The permissions are stored in request extensions
+    /// and checked against the ones required by the route.
+    pub fn permissions<F, I, S>(mut self, permissions: F) -> Self where
+        F: Fn(&P) -> I + Send + Sync + 'static,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.permissions = Some(Arc::new(move |principal| Permissions::new(permissions(principal))));
+        self
+    }
+
+    /// Extracts the credentials.
+    /// Returns `Err` if the credentials are present, but malformed.
+    fn credentials(&self, request: &Request) -> Result<Option<Credentials>, ()> {
+        let headers = request.headers();
+        match self.scheme {
+            Scheme::Basic => match headers.get::<Authorization<Basic>>() {
+                Some(auth) => Ok(Some(Credentials::Basic {
+                    username: auth.0.username.clone(),
+                    password: auth.0.password.clone(),
+                })),
+                None if uses_scheme(headers, "Basic") => Err(()),
+                None => Ok(None),
+            },
+            Scheme::Bearer => match headers.get::<Authorization<Bearer>>() {
+                Some(auth) => Ok(Some(Credentials::Bearer(auth.0.token.clone()))),
+                None if uses_scheme(headers, "Bearer") => Err(()),
+                None => Ok(None),
+            },
+            Scheme::ApiKeyHeader(ref name) => match headers.get_raw(name) {
+                Some(raw) => raw.one()
+                    .and_then(|value| String::from_utf8(value.to_vec()).ok())
+                    .map(|value| Some(Credentials::ApiKey(value)))
+                    .ok_or(()),
+                None => Ok(None),
+            },
+            Scheme::ApiKeyQuery(ref name) => match request.uri().query() {
+                Some(query) => serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+                    .map(|params| params.into_iter()
+                        .find(|param| &param.0 == name)
+                        .map(|(_, value)| Credentials::ApiKey(value)))
+                    .map_err(|_| ()),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Creates `401` error with `WWW-Authenticate` challenge.
+    fn unauthorized(&self, details: &str, error: Option<&str>) -> Error {
+        Challenge(self.challenge(error)).unauthorized(details)
+    }
+
+    /// Returns `WWW-Authenticate` challenge.
+    ///
+    /// `error` is the error code of Bearer challenge (i.e. `invalid_token`).
+    fn challenge(&self, error: Option<&str>) -> String {
+        let realm = quote(&self.realm);
+        match self.scheme {
+            Scheme::Basic => format!("Basic realm={}, charset=\"UTF-8\"", realm),
+            Scheme::Bearer => match error {
+                Some(error) => format!("Bearer realm={}, error=\"{}\"", realm, error),
+                None => format!("Bearer realm={}", realm),
+            },
+            Scheme::ApiKeyHeader(ref name) => format!("ApiKey realm={}, in=\"header\", name={}", realm, quote(name)),
+            Scheme::ApiKeyQuery(ref name) => format!("ApiKey realm={}, in=\"query\", name={}", realm, quote(name)),
+        }
+    }
+}
+
+/// `WWW-Authenticate` challenge of the authenticator that let the request through without credentials.
+///
+/// Stored in request extensions, so that the request can be rejected
+/// if the route requires permissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Challenge(String);
+
+impl Challenge {
+    /// Creates `401` error with the challenge.
+    pub(crate) fn unauthorized(&self, details: &str) -> Error {
+        let mut error = Error::unauthorized(details);
+        error.headers_mut().set_raw("WWW-Authenticate", self.0.clone());
+        error
+    }
+}
+
+/// Formats a quoted string of `WWW-Authenticate` challenge.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<P: Send + Sync + 'static> Middleware for Authenticator<P> {
+    fn on_request(&self, mut request: Request) -> MiddlewareResult {
+        let credentials = match self.credentials(&request) {
+            Ok(Some(credentials)) => credentials,
+            Ok(None) if self.optional => {
+                let challenge = Challenge(self.challenge(None));
+                request.extensions_mut().insert(challenge);
+                return Box::new(future::ok(request));
+            },
+            Ok(None) => return Box::new(future::err(self.unauthorized("Missing credentials.", None))),
+            Err(()) => return Box::new(future::err(self.unauthorized("Malformed credentials.", Some("invalid_request")))),
+        };
+
+        let invalid = self.unauthorized("Invalid credentials.", Some("invalid_token"));
+        let permissions = self.permissions.clone();
+        Box::new((self.verify)(credentials).and_then(move |principal| match principal {
+            Some(principal) => {
+                let permissions = permissions.map_or_else(Permissions::default, |permissions| permissions(&principal));
+                request.extensions_mut().insert(permissions);
+                request.extensions_mut().insert(principal);
+                Ok(request)
+            },
+            None => Err(invalid),
+        }))
+    }
+}
+
+/// Checks whether any `Authorization` header uses given scheme (case-insensitive).
+///
+/// Credentials of other schemes are not meant for the authenticator and are ignored.
+fn uses_scheme(headers: &hyper::Headers, scheme: &str) -> bool {
+    headers.get_raw("Authorization").into_iter()
+        .flat_map(|raw| raw.iter())
+        .filter_map(|value| value.split(|byte| *byte == b' ').next())
+        .any(|name| name.eq_ignore_ascii_case(scheme.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use hyper;
+
+    use config::Config;
+    use error::Error;
+    use middleware::Middleware;
+    use request::Request;
+    use router::Router;
+    use testing::TestClient;
+    use super::Authenticator;
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    fn request(uri: &str, auth: Option<&str>) -> Request {
+        request_with(uri, "Authorization", auth)
+    }
+
+    fn request_with(uri: &str, header: &str, value: Option<&str>) -> Request {
+        let mut request = hyper::Request::new(hyper::Method::Get, uri.parse().unwrap());
+        if let Some(value) = value {
+            request.headers_mut().set_raw(header.to_owned(), value);
+        }
+        Request::new(request, ())
+    }
+
+    fn bearer() -> Authenticator<User> {
+        Authenticator::bearer("api", |token: String| Ok(if token == "secret" { Some(User("alice".into())) } else { None }))
+    }
+
+    #[test]
+    fn should_authenticate_bearer_token() {
+        let req = bearer().on_request(request("/", Some("Bearer secret"))).wait().unwrap();
+        assert_eq!(req.extensions().get::<User>(), Some(&User("alice".into())));
+
+        let error = bearer().on_request(request("/", Some("Bearer invalid"))).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+        assert_eq!(
+            error.headers.unwrap().get_raw("WWW-Authenticate").unwrap(),
+            "Bearer realm=\"api\", error=\"invalid_token\""
+        );
+
+        let error = bearer().on_request(request("/", None)).wait().unwrap_err();
+        assert_eq!(error.headers.unwrap().get_raw("WWW-Authenticate").unwrap(), "Bearer realm=\"api\"");
+        assert!(bearer().optional().on_request(request("/", None)).wait().is_ok());
+    }
+
+    #[test]
+    fn should_authenticate_basic_and_api_keys() {
+        let basic = Authenticator::basic("admin", |user: String, password: Option<String>| {
+            Ok(if password.as_deref() == Some("pass") { Some(User(user)) } else { None })
+        });
+        let req = basic.on_request(request("/", Some("Basic Ym9iOnBhc3M="))).wait().unwrap();
+        assert_eq!(req.extensions().get::<User>(), Some(&User("bob".into())));
+
+        let verify = |key: String| Ok(if key == "k1" { Some(User("key".into())) } else { None });
+        let query = Authenticator::api_key_query("api_key", verify);
+        assert!(query.on_request(request("/?x=1&api_key=k1", None)).wait().is_ok());
+        let error = query.on_request(request("/?api_key=k2", None)).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+
+        let header = Authenticator::api_key_header("X-Api-Key", verify);
+        assert!(header.on_request(request_with("/", "X-Api-Key", Some("k1"))).wait().is_ok());
+    }
+
+    #[test]
+    fn should_reject_malformed_credentials() {
+        // Credentials of other schemes are ignored by optional authenticators.
+        let passed = bearer().optional().on_request(request("/", Some("Basic Ym9iOnBhc3M="))).wait().unwrap();
+        assert!(passed.extensions().get::<User>().is_none());
+
+        let error = bearer().optional().on_request(request("/", Some("bearer"))).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+        assert_eq!(
+            error.headers.unwrap().get_raw("WWW-Authenticate").unwrap(),
+            "Bearer realm=\"api\", error=\"invalid_request\""
+        );
+
+        let realm = Authenticator::bearer("my \"api\\", |_: String| Ok(Some(User("alice".into()))));
+        let error = realm.on_request(request("/", None)).wait().unwrap_err();
+        assert_eq!(error.headers.unwrap().get_raw("WWW-Authenticate").unwrap(), r#"Bearer realm="my \"api\\""#);
+    }
+
+    #[test]
+    fn should_inherit_or_override_authenticator() {
+        let verify = |token: String| Ok(if token == "secret" { Some(User("alice".into())) } else { None });
+        let mut public = Router::with_config(Config::new().auth(Authenticator::bearer("api", verify).optional()));
+        public.get("/hello", |request: Request<_>| Ok(request.extensions().get::<User>().is_some()) as Result<_, Error>);
+        let mut private = Router::new();
+        private.get("/hello", |_: Request<_>| Ok(true) as Result<_, Error>);
+        let mut router = Router::with_config(Config::new().auth(Authenticator::bearer("api", verify)));
+        router.add("/public", public);
+        router.add("/private", private);
+        let client = TestClient::new(router);
+
+        assert_eq!(client.get("/public/hello").send().status(), hyper::StatusCode::Ok);
+        assert_eq!(client.get("/private/hello").header("Authorization", "Bearer secret").send().status(), hyper::StatusCode::Ok);
+        let response = client.get("/private/hello").send();
+        assert_eq!(response.status(), hyper::StatusCode::Unauthorized);
+        assert_eq!(response.header("WWW-Authenticate"), Some("Bearer realm=\"api\""));
+        let response = client.get("/public/hello").header("Authorization", "Bearer invalid").send();
+        assert_eq!(response.status(), hyper::StatusCode::Unauthorized);
+    }
+
+    #[test]
+    fn should_require_route_permissions() {
+        let verify = |token: String| Ok(Some(User(token)));
+        let auth = Authenticator::bearer("api", verify).permissions(|user: &User| match &*user.0 {
+            "admin" => vec!["products:read", "products:write"],
+            _ => vec!["products:read"],
+        });
+        let mut products = Router::new();
+        products.get("/", |_: Request<_>| Ok("list") as Result<_, Error>);
+        products.post("/", |_: Request<_>| Ok("created") as Result<_, Error>).require("products:write");
+        let mut router = Router::with_config(Config::new().auth(auth).require("products:read"));
+        router.add("/products", products);
+        assert!(router.routes().contains("POST / [requires: products:read, products:write]"), "{}", router.routes());
+        let client = TestClient::new(router);
+
+        assert_eq!(client.get("/products/").header("Authorization", "Bearer alice").send().status(), hyper::StatusCode::Ok);
+        assert_eq!(client.post("/products/").header("Authorization", "Bearer alice").send().status(), hyper::StatusCode::Forbidden);
+        assert_eq!(client.post("/products/").header("Authorization", "Bearer admin").send().status(), hyper::StatusCode::Ok);
+    }
+
+    #[test]
+    fn should_combine_permissions_of_nested_routers() {
+        let verify = |token: String| Ok(Some(User(token)));
+        let auth = Authenticator::bearer("api", verify).optional().permissions(|user: &User| match &*user.0 {
+            "admin" => vec!["products:read", "products:write"],
+            _ => vec!["products:write"],
+        });
+        let mut products = Router::with_config(Config::new().require("products:write"));
+        products.post("/", |_: Request<_>| Ok("created") as Result<_, Error>);
+        let mut api = Router::with_config(Config::new().require("products:read"));
+        api.add("/products", products);
+        let mut router = Router::with_config(Config::new().auth(auth));
+        router.add("/api", api);
+        assert!(router.routes().contains("POST / [requires: products:write, products:read]"), "{}", router.routes());
+        let client = TestClient::new(router);
+
+        assert_eq!(client.post("/api/products/").header("Authorization", "Bearer admin").send().status(), hyper::StatusCode::Ok);
+        assert_eq!(client.post("/api/products/").header("Authorization", "Bearer alice").send().status(), hyper::StatusCode::Forbidden);
+        let response = client.post("/api/products/").send();
+        assert_eq!(response.status(), hyper::StatusCode::Unauthorized);
+        assert_eq!(response.header("WWW-Authenticate"), Some("Bearer realm=\"api\""));
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use hyper;
+
+use error::Error;
+
+/// Limits the number of requests processed concurrently.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    limit: Option<usize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Limiter {
+    /// Creates a limiter using given in-flight requests counter.
+    pub fn new(limit: Option<usize>, in_flight: Arc<AtomicUsize>) -> Self {
+        Limiter { limit, in_flight }
+    }
+
+    /// Acquires a slot for the request if the limit is not reached yet.
+    /// Otherwise returns `503` error.
+    pub fn process(&self) -> Result<Guard, Error> {
+        self.acquire().ok_or_else(|| {
+            Error::service_unavailable("Too many requests are being processed at the moment.")
+                .with_header(hyper::header::RetryAfter::Delay(Duration::from_secs(1)))
+        })
+    }
+
+    /// Acquires a slot if the limit is not reached yet.
+    /// The slot is released when the guard is dropped.
+    pub fn acquire(&self) -> Option<Guard> {
+        let guard = Guard::new(&self.in_flight);
+        match self.limit {
+            Some(limit) if guard.1 >= limit => None,
+            _ => Some(guard),
+        }
+    }
+}
+
+/// Slot acquired from the limiter.
+#[derive(Debug)]
+pub struct Guard(Arc<AtomicUsize>, usize);
+
+impl Guard {
+    /// Increments given counter until the guard is dropped.
+    pub fn new(counter: &Arc<AtomicUsize>) -> Self {
+        let previous = counter.fetch_add(1, Ordering::SeqCst);
+        Guard(counter.clone(), previous)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use hyper;
+    use super::Limiter;
+
+    #[test]
+    fn should_reject_requests_over_limit() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let limiter = Limiter::new(Some(1), in_flight.clone());
+
+        let first = limiter.process().unwrap();
+        assert_eq!(in_flight.load(Ordering::SeqCst), 1);
+        let rejected = limiter.process().expect_err("Over the limit");
+        assert_eq!(rejected.code, hyper::StatusCode::ServiceUnavailable);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 1);
+
+        drop(first);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+        assert!(limiter.process().is_ok());
+    }
+}
@@ -1,4 +1,9 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use Headers;
+use metrics::Metrics;
+use middleware::{Middleware, Middlewares};
 
 type InternalHeaders = Vec<(String, Vec<Vec<u8>>)>;
 /// A router configuration.
@@ -7,6 +12,15 @@ pub struct Config {
     handle_head: Option<bool>,
     handle_options: Option<bool>,
     extra_headers: Option<InternalHeaders>,
+    middlewares: Option<Middlewares>,
+    request_id: Option<bool>,
+    request_id_header: Option<String>,
+    metrics: Option<Arc<Metrics>>,
+    timeout: Option<Duration>,
+    max_concurrent_requests: Option<usize>,
+    auth: Option<Middlewares>,
+    permissions: Option<Vec<String>>,
+    strict_routes: Option<bool>,
 }
 
 impl Config {
@@ -52,12 +66,118 @@ impl Config {
         self
     }
 
+    /// Adds a middleware invoked for every request handled by the router.
+    /// Middlewares are invoked in the order of registration.
+    ///
+    /// NOTE: Middlewares set on a sub-router replace the ones inherited from the parent router.
+    pub fn middleware<M: Middleware>(mut self, middleware: M) -> Self {
+        self.middlewares.get_or_insert_with(Default::default).0.push(Arc::new(middleware));
+        self
+    }
+
+    /// Authenticates every request handled by the router (before other middlewares).
+    ///
+    /// Sub-routers composed with `Router::add` inherit the authenticator
+    /// unless they have their own one.
+    pub fn auth<A: Middleware>(mut self, authenticator: A) -> Self {
+        self.auth = Some(Middlewares(vec![Arc::new(authenticator)]));
+        self
+    }
+
+    /// Requires given permission (role or scope) for every route of the router.
+    ///
+    /// Permissions are provided by the authenticator (see `Authenticator::permissions`),
+    /// requests lacking any of the required ones are rejected with `403`.
+    /// Requests without credentials are rejected with `401`.
+    ///
+    /// Sub-routers composed with `Router::add` require the permissions of the parent router as well.
+    pub fn require(mut self, permission: &str) -> Self {
+        self.permissions.get_or_insert_with(Vec::new).push(permission.into());
+        self
+    }
+
+    /// Set to true if you want every request to have an id.
+    ///
+    /// The id is read from the incoming request header (see `request_id_header`)
+    /// or generated if the header is missing. It's available via `Request::request_id`,
+    /// returned in the response header and embedded in every error response.
+    ///
+    /// NOTE: Only effective for the top-level router.
+    pub fn request_id<T: Into<Option<bool>>>(mut self, request_id: T) -> Self {
+        self.request_id = request_id.into();
+        self
+    }
+
+    /// Name of the header containing request id. Defaults to `X-Request-Id`.
+    pub fn request_id_header<T: Into<Option<String>>>(mut self, request_id_header: T) -> Self {
+        self.request_id_header = request_id_header.into();
+        self
+    }
+
+    /// Set to true to refuse to start the server if there are conflicting routes
+    /// (see `Router::conflicts`).
+    ///
+    /// NOTE: Only effective for the top-level router.
+    pub fn strict_routes<T: Into<Option<bool>>>(mut self, strict_routes: T) -> Self {
+        self.strict_routes = strict_routes.into();
+        self
+    }
+
+    /// Maximal time the handler (including middlewares) can take to process the request.
+    ///
+    /// Handlers exceeding the timeout are cancelled and `504` is returned.
+    /// Can be overridden for particular routes (see `Route::timeout`).
+    ///
+    /// NOTE: The timeout relies on the timer of the event loop the request is processed on
+    /// (servers started by the router and `TestClient` have one). Responses driven without
+    /// an event loop (i.e. `Server::call(..).wait()`) never time out.
+    pub fn timeout<T: Into<Option<Duration>>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Maximal number of requests processed concurrently by the server.
+    ///
+    /// Requests over the limit are immediately rejected with `503`.
+    /// Particular routes can be limited with `Route::max_concurrent_requests`.
+    ///
+    /// NOTE: Only effective for the top-level router.
+    pub fn max_concurrent_requests<T: Into<Option<usize>>>(mut self, max: T) -> Self {
+        self.max_concurrent_requests = max.into();
+        self
+    }
+
+    /// Registry collecting metrics of handled requests.
+    pub(crate) fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Use other config settings for unset options.
     pub fn add(&mut self, other: &Config) {
         let other = other.to_owned();
         self.handle_head = self.handle_head.or(other.handle_head);
         self.handle_options = self.handle_options.or(other.handle_options);
         self.extra_headers = self.extra_headers.take().or(other.extra_headers);
+        self.middlewares = self.middlewares.take().or(other.middlewares);
+        self.request_id = self.request_id.or(other.request_id);
+        self.request_id_header = self.request_id_header.take().or(other.request_id_header);
+        self.metrics = self.metrics.take().or(other.metrics);
+        self.timeout = self.timeout.or(other.timeout);
+        self.max_concurrent_requests = self.max_concurrent_requests.or(other.max_concurrent_requests);
+        self.auth = self.auth.take().or(other.auth);
+        self.permissions = match (self.permissions.take(), other.permissions) {
+            (Some(mut permissions), Some(other)) => {
+                for permission in other {
+                    if !permissions.contains(&permission) {
+                        permissions.push(permission);
+                    }
+                }
+                Some(permissions)
+            },
+            (permissions, other) => permissions.or(other),
+        };
+        self.strict_routes = self.strict_routes.or(other.strict_routes);
     }
 
     /// Convert this config into materialized config.
@@ -68,6 +188,15 @@ impl Config {
             handle_head: self.handle_head.clone().unwrap_or(base.handle_head),
             handle_options: self.handle_options.clone().unwrap_or(base.handle_options),
             extra_headers: self.extra_headers.clone().unwrap_or(base.extra_headers),
+            middlewares: self.middlewares.clone().unwrap_or(base.middlewares),
+            request_id: self.request_id.unwrap_or(base.request_id),
+            request_id_header: self.request_id_header.clone().unwrap_or(base.request_id_header),
+            metrics: self.metrics.clone().or(base.metrics),
+            timeout: self.timeout.or(base.timeout),
+            max_concurrent_requests: self.max_concurrent_requests.or(base.max_concurrent_requests),
+            auth: self.auth.clone().or(base.auth),
+            permissions: self.permissions.clone().unwrap_or(base.permissions),
+            strict_routes: self.strict_routes.unwrap_or(base.strict_routes),
         }
     }
 }
@@ -77,6 +206,15 @@ pub(crate) struct MaterializedConfig {
     pub handle_head: bool,
     pub handle_options: bool,
     pub extra_headers: InternalHeaders,
+    pub middlewares: Middlewares,
+    pub request_id: bool,
+    pub request_id_header: String,
+    pub metrics: Option<Arc<Metrics>>,
+    pub timeout: Option<Duration>,
+    pub max_concurrent_requests: Option<usize>,
+    pub auth: Option<Middlewares>,
+    pub permissions: Vec<String>,
+    pub strict_routes: bool,
 }
 
 impl From<MaterializedConfig> for Config {
@@ -85,6 +223,15 @@ impl From<MaterializedConfig> for Config {
             handle_head: Some(conf.handle_head),
             handle_options: Some(conf.handle_options),
             extra_headers: Some(conf.extra_headers),
+            middlewares: Some(conf.middlewares),
+            request_id: Some(conf.request_id),
+            request_id_header: Some(conf.request_id_header),
+            metrics: conf.metrics,
+            timeout: conf.timeout,
+            max_concurrent_requests: conf.max_concurrent_requests,
+            auth: conf.auth,
+            permissions: Some(conf.permissions),
+            strict_routes: Some(conf.strict_routes),
         }
     }
 }
@@ -95,6 +242,15 @@ impl Default for MaterializedConfig {
             handle_head: true,
             handle_options: true,
             extra_headers: Default::default(),
+            middlewares: Default::default(),
+            request_id: false,
+            request_id_header: "X-Request-Id".into(),
+            metrics: None,
+            timeout: None,
+            max_concurrent_requests: None,
+            auth: None,
+            permissions: vec![],
+            strict_routes: false,
         }
     }
 }
@@ -13,6 +13,12 @@ pub struct Error {
     pub message: String,
     /// Error details
     pub details: String,
+    /// Additional structured error data.
+    pub data: Option<serde_json::Value>,
+    /// Id of the request that caused the error.
+    pub request_id: Option<String>,
+    /// Additional headers of the error response.
+    pub headers: Option<Box<hyper::Headers>>,
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -20,6 +26,10 @@ struct Serializable {
     pub code: u16,
     pub message: String,
     pub details: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl Into<Response> for Error {
@@ -35,12 +45,19 @@ impl Into<hyper::Response> for Error {
             code: self.code.as_u16(),
             message: self.message,
             details: self.details,
+            data: self.data,
+            request_id: self.request_id,
         }).expect("The serialization is infallible; qed");
 
-        hyper::Response::new()
+        let mut response = hyper::Response::new()
             .with_status(self.code)
             .with_header(header::ContentType::json())
-            .with_body(serialized)
+            .with_header(header::ContentLength(serialized.len() as u64))
+            .with_body(serialized);
+        if let Some(headers) = self.headers {
+            response.headers_mut().extend(headers.iter());
+        }
+        response
     }
 }
 
@@ -51,6 +68,9 @@ impl Error {
             code: StatusCode::InternalServerError,
             message: message.into(),
             details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
         }
     }
 
@@ -60,6 +80,9 @@ impl Error {
             code: StatusCode::NotFound,
             message: "Requested resource was not found.".to_owned(),
             details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
         }
     }
 
@@ -69,6 +92,33 @@ impl Error {
             code: StatusCode::BadRequest,
             message: message.into(),
             details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 401 unauthorized error.
+    pub fn unauthorized<T: Into<String>>(details: T) -> Self {
+        Error {
+            code: StatusCode::Unauthorized,
+            message: "Authentication is required.".to_owned(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 403 forbidden error.
+    pub fn forbidden<T: Into<String>>(details: T) -> Self {
+        Error {
+            code: StatusCode::Forbidden,
+            message: "Access to the resource is forbidden.".to_owned(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
         }
     }
 
@@ -78,6 +128,80 @@ impl Error {
             code: StatusCode::MethodNotAllowed,
             message: message.into(),
             details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 422 unprocessable entity error.
+    pub fn unprocessable_entity<A: Into<String>, B: Into<String>>(message: A, details: B) -> Self {
+        Error {
+            code: StatusCode::UnprocessableEntity,
+            message: message.into(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 503 service unavailable error.
+    pub fn service_unavailable<T: Into<String>>(details: T) -> Self {
+        Error {
+            code: StatusCode::ServiceUnavailable,
+            message: "Service is temporarily unavailable.".to_owned(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 504 gateway timeout error.
+    pub fn timeout<T: Into<String>>(details: T) -> Self {
+        Error {
+            code: StatusCode::GatewayTimeout,
+            message: "Request processing timed out.".to_owned(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
+        }
+    }
+
+    /// Generate 429 too many requests error.
+    pub fn too_many_requests<T: Into<String>>(details: T) -> Self {
+        Error {
+            code: StatusCode::TooManyRequests,
+            message: "Too many requests.".to_owned(),
+            details: details.into(),
+            data: None,
+            request_id: None,
+            headers: None,
         }
     }
+
+    /// Attach id of the request that caused the error.
+    pub(crate) fn with_request_id<T: Into<Option<String>>>(mut self, request_id: T) -> Self {
+        self.request_id = request_id.into();
+        self
+    }
+
+    /// Attach additional header to the error response.
+    pub fn with_header<H: header::Header>(mut self, header: H) -> Self {
+        self.headers_mut().set(header);
+        self
+    }
+
+    /// Returns mutable reference to additional headers of the error response.
+    pub fn headers_mut(&mut self) -> &mut hyper::Headers {
+        self.headers.get_or_insert_with(Default::default)
+    }
+
+    /// Attach additional structured data to the error.
+    pub fn with_data<T: Into<serde_json::Value>>(mut self, data: T) -> Self {
+        self.data = Some(data.into());
+        self
+    }
 }
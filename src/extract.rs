@@ -0,0 +1,314 @@
+//! Typed request extractors.
+//!
+//! Extractors allow handlers to declare what parts of the request they need
+//! instead of processing the `Request` manually:
+//!
+//! ```rust,ignore
+//! use resty::extract::{self, Path, Json, Header};
+//!
+//! router.put("/{id}", |(Path(id), Json(product), Header(auth)): (Path<usize>, Json<Product>, Header<Authorization<String>>)| {
+//!     products.update(id, product)
+//! });
+//! ```
+//!
+//! Handlers that need the whole request simply declare it as the only argument
+//! (i.e. `|request: Request<_>| ...`).
+
+use std::str::FromStr;
+use std::sync::Arc;
+use futures::{future, Future, IntoFuture, Stream};
+use hyper::header;
+use serde;
+use serde_urlencoded;
+
+use error::Error;
+use request::{self, Request};
+use request::params::DynamicParams;
+
+/// A type that can be extracted from the request.
+pub trait FromRequest<P>: Sized {
+    /// Extraction result.
+    type Future: Future<Item = Self, Error = Error>;
+
+    /// Extracts the value from given request.
+    fn from_request(request: &mut Request<P>) -> Self::Future;
+}
+
+/// Converts a function accepting extractors into a function accepting the request.
+///
+/// Extraction failures are returned as errors without invoking the function.
+/// Not needed for `Router::on` (and `Router::get`, `Router::post`, etc), which accept such functions directly.
+pub fn handler<P, T, F, I>(fun: F) -> impl Fn(Request<P>) -> Box<Future<Item = I::Item, Error = Error>> where
+    T: FromRequest<P> + 'static,
+    T::Future: 'static,
+    F: Fn(T) -> I + Sync + Send + 'static,
+    I: IntoFuture,
+    I::Error: Into<Error> + 'static,
+    I::Future: 'static,
+{
+    let fun = Arc::new(fun);
+    move |mut request| {
+        let fun = fun.clone();
+        Box::new(T::from_request(&mut request).and_then(move |args| {
+            fun(args).into_future().map_err(Into::into)
+        }))
+    }
+}
+
+/// The request itself.
+///
+/// Meant to be the only argument of the handler, other extractors would see an empty request.
+impl<P> FromRequest<P> for Request<P> {
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        future::ok(request.take())
+    }
+}
+
+/// Path parameters extractor.
+///
+/// `Path<T>` for any parameters type `T: Clone` returns all parsed parameters
+/// (useful for `url!` declarations), while for dynamic parameters
+/// `Path<T: FromStr>` parses the value of the only declared parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path<T>(pub T);
+
+impl<P: Clone> FromRequest<P> for Path<P> {
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        future::ok(Path(request.params().clone()))
+    }
+}
+
+impl<T> FromRequest<DynamicParams> for Path<T> where
+    T: FromStr,
+    T::Err: ::std::fmt::Debug,
+{
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<DynamicParams>) -> Self::Future {
+        let params = request.params();
+        let names = params.names();
+        if names.len() != 1 {
+            return future::err(Error::internal(
+                "Path extractor requires exactly one parameter. That's most likely a bug in the handler.",
+                format!("Declared parameters: {:?}", names),
+            ));
+        }
+
+        future::result(params.get(names[0]).map(Path).map_err(Into::into))
+    }
+}
+
+/// Query string extractor.
+///
+/// Deserializes the query string into `T`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query<T>(pub T);
+
+impl<P, T> FromRequest<P> for Query<T> where
+    T: for<'a> serde::de::Deserialize<'a>,
+{
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        let query = request.uri().query().unwrap_or("");
+        future::result(serde_urlencoded::from_str(query).map(Query).map_err(|err| Error::bad_request(
+            "Unable to parse query string.",
+            format!("{:?}", err),
+        )))
+    }
+}
+
+/// JSON body extractor.
+///
+/// Reads the body of the request and deserializes it from JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<P, T> FromRequest<P> for Json<T> where
+    T: for<'a> serde::de::Deserialize<'a> + 'static,
+{
+    type Future = Box<Future<Item = Self, Error = Error>>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        Box::new(request.take_body().concat2().then(|chunk| {
+            request::deserialize(chunk).map(Json).map_err(Into::into)
+        }))
+    }
+}
+
+/// Typed header extractor.
+///
+/// Fails with `400 Bad Request` if the header is missing or invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header<H>(pub H);
+
+impl<P, H> FromRequest<P> for Header<H> where
+    H: header::Header + Clone,
+{
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        match request.headers().get::<H>() {
+            Some(h) => future::ok(Header(h.clone())),
+            None => future::err(Error::bad_request(
+                format!("Missing or invalid header: {}", H::header_name()),
+                "",
+            )),
+        }
+    }
+}
+
+/// Shared state extractor.
+///
+/// Returns the nearest state of given type registered with `Router::with_state`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<S>(pub Arc<S>);
+
+impl<P, S> FromRequest<P> for State<S> where
+    S: Send + Sync + 'static,
+{
+    type Future = future::FutureResult<Self, Error>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        future::result(request.state().map(State))
+    }
+}
+
+impl<P, A, B> FromRequest<P> for (A, B) where
+    A: FromRequest<P>,
+    B: FromRequest<P>,
+{
+    type Future = future::Join<A::Future, B::Future>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        let a = A::from_request(request);
+        a.join(B::from_request(request))
+    }
+}
+
+impl<P, A, B, C> FromRequest<P> for (A, B, C) where
+    A: FromRequest<P>,
+    B: FromRequest<P>,
+    C: FromRequest<P>,
+{
+    type Future = future::Join3<A::Future, B::Future, C::Future>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        let a = A::from_request(request);
+        let b = B::from_request(request);
+        a.join3(b, C::from_request(request))
+    }
+}
+
+impl<P, A, B, C, D> FromRequest<P> for (A, B, C, D) where
+    A: FromRequest<P>,
+    B: FromRequest<P>,
+    C: FromRequest<P>,
+    D: FromRequest<P>,
+{
+    type Future = future::Join4<A::Future, B::Future, C::Future, D::Future>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        let a = A::from_request(request);
+        let b = B::from_request(request);
+        let c = C::from_request(request);
+        a.join4(b, c, D::from_request(request))
+    }
+}
+
+impl<P, A, B, C, D, E> FromRequest<P> for (A, B, C, D, E) where
+    A: FromRequest<P>,
+    B: FromRequest<P>,
+    C: FromRequest<P>,
+    D: FromRequest<P>,
+    E: FromRequest<P>,
+{
+    type Future = future::Join5<A::Future, B::Future, C::Future, D::Future, E::Future>;
+
+    fn from_request(request: &mut Request<P>) -> Self::Future {
+        let a = A::from_request(request);
+        let b = B::from_request(request);
+        let c = C::from_request(request);
+        let d = D::from_request(request);
+        a.join5(b, c, d, E::from_request(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use hyper::{self, header};
+
+    use error::Error;
+    use request::params::Parser;
+    use request::{Params, Request};
+    use router::Router;
+    use testing::TestClient;
+    use super::{handler, FromRequest, Header, Json, Path, Query};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Data {
+        id: usize,
+    }
+
+    fn request(uri: &str, prefix: &str, body: &str) -> Request<::request::params::DynamicParams> {
+        let params: Params = prefix.into();
+        let mut request = hyper::Request::new(hyper::Method::Post, uri.parse().unwrap());
+        request.headers_mut().set(header::ContentType::json());
+        request.set_body(body.to_owned());
+        let parsed = params.parser.parse(request.uri(), params.prefix.len()).unwrap();
+        Request::new(request, parsed)
+    }
+
+    #[test]
+    fn should_extract_tuple_of_values() {
+        let mut req = request("http://localhost/test/5?id=3", "/test/{id}", r#"{"id":7}"#);
+
+        let (Path(id), Query(query), Json(body), Header(content_type)) =
+            <(Path<usize>, Query<Data>, Json<Data>, Header<header::ContentType>)>::from_request(&mut req)
+            .wait()
+            .unwrap();
+
+        assert_eq!(id, 5);
+        assert_eq!(query, Data { id: 3 });
+        assert_eq!(body, Data { id: 7 });
+        assert_eq!(content_type, header::ContentType::json());
+    }
+
+    #[test]
+    fn should_map_extraction_failures_to_errors() {
+        let handler = handler(|(Path(id), Json(data)): (Path<usize>, Json<Data>)| {
+            Ok(id + data.id) as Result<_, ::Error>
+        });
+
+        assert_eq!(handler(request("http://localhost/5", "/{id}", r#"{"id":1}"#)).wait().unwrap(), 6usize);
+
+        let error = handler(request("http://localhost/x", "/{id}", r#"{"id":1}"#)).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::BadRequest);
+
+        let error = handler(request("http://localhost/5", "/{id}", "{")).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::BadRequest);
+
+        let mut req = request("http://localhost/5", "/{id}", "");
+        let error = Header::<header::Authorization<String>>::from_request(&mut req).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn should_accept_extractor_handlers() {
+        use super::State;
+
+        let mut router = Router::new().with_state(10usize);
+        router.post("/{id}", |(Path(id), Json(value), State(offset)): (Path<usize>, Json<usize>, State<usize>)| {
+            Ok(id + value + *offset) as Result<_, Error>
+        });
+        let client = TestClient::new(router);
+
+        assert_eq!(client.post("/5").body("2").send().text(), "17");
+        assert_eq!(client.post("/x").send().status(), hyper::StatusCode::BadRequest);
+    }
+}
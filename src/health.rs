@@ -0,0 +1,243 @@
+//! Liveness and readiness checks.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use futures::{future, Future, IntoFuture};
+use hyper::{self, header};
+use serde_json;
+
+use timeout::{self, timeout};
+
+/// Default timeout of a single check.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Status of the service or a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Everything is fine.
+    Healthy,
+    /// Check failed.
+    Unhealthy,
+}
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    /// Check status.
+    pub status: Status,
+    /// Additional details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl CheckResult {
+    /// Healthy result without details.
+    pub fn healthy() -> Self {
+        CheckResult {
+            status: Status::Healthy,
+            details: None,
+        }
+    }
+
+    /// Unhealthy result with given details.
+    pub fn unhealthy<T: Into<serde_json::Value>>(details: T) -> Self {
+        CheckResult {
+            status: Status::Unhealthy,
+            details: Some(details.into()),
+        }
+    }
+
+    /// Attaches details to the result.
+    pub fn with_details<T: Into<serde_json::Value>>(mut self, details: T) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+type CheckFuture = Box<Future<Item = CheckResult, Error = ()>>;
+
+/// A readiness check.
+pub struct Check {
+    name: String,
+    required: bool,
+    timeout: Duration,
+    check: Box<Fn() -> CheckFuture + Send + Sync>,
+}
+
+impl fmt::Debug for Check {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Check")
+            .field("name", &self.name)
+            .field("required", &self.required)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Check {
+    /// Creates a new required check with default timeout (5s).
+    ///
+    /// Errors returned by the check are treated as unhealthy result.
+    pub fn new<F, I, E>(name: &str, check: F) -> Self where
+        F: Fn() -> I + Send + Sync + 'static,
+        I: IntoFuture<Item = CheckResult, Error = E>,
+        I::Future: 'static,
+        E: fmt::Display,
+    {
+        Check {
+            name: name.into(),
+            required: true,
+            timeout: DEFAULT_TIMEOUT,
+            check: Box::new(move || Box::new(check().into_future().or_else(|err| {
+                Ok(CheckResult::unhealthy(err.to_string()))
+            }))),
+        }
+    }
+
+    /// Set to false if failure of this check should not make the service unready.
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Time after which the check is considered unhealthy.
+    ///
+    /// As with `Config::timeout`, the check never times out when polled without an event loop.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Result of a single check in the readiness report.
+#[derive(Debug, Serialize)]
+struct CheckReport {
+    name: String,
+    required: bool,
+    #[serde(flatten)]
+    result: CheckResult,
+    duration_ms: f64,
+}
+
+/// Readiness report.
+#[derive(Debug, Serialize)]
+struct Report {
+    status: Status,
+    checks: Vec<CheckReport>,
+}
+
+/// A set of readiness checks served by `Router::health`.
+///
+/// The handle can be cloned and checks can be registered after the router is built.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    checks: Arc<RwLock<Vec<Arc<Check>>>>,
+}
+
+impl Health {
+    /// Registers a readiness check.
+    pub fn check(&self, check: Check) {
+        self.checks.write().unwrap_or_else(|e| e.into_inner()).push(Arc::new(check));
+    }
+
+    /// Liveness response.
+    pub(crate) fn live(&self) -> hyper::Response {
+        response(hyper::StatusCode::Ok, &Report {
+            status: Status::Healthy,
+            checks: vec![],
+        })
+    }
+
+    /// Runs all checks and returns readiness response.
+    pub(crate) fn ready(&self) -> Box<Future<Item = hyper::Response, Error = ()>> {
+        let checks = self.checks.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let checks = checks.into_iter().map(|check| {
+            let started = Instant::now();
+            timeout((check.check)(), check.timeout).then(move |result| {
+                let result = match result {
+                    Ok(Some(result)) => result,
+                    Ok(None) => CheckResult::unhealthy("Check timed out."),
+                    Err(()) => CheckResult::unhealthy("Check failed."),
+                };
+                Ok(CheckReport {
+                    name: check.name.clone(),
+                    required: check.required,
+                    result,
+                    duration_ms: timeout::secs(started.elapsed()) * 1_000.0,
+                })
+            })
+        }).collect::<Vec<_>>();
+
+        Box::new(future::join_all(checks).map(|checks| {
+            let failed = checks.iter().any(|check| check.required && check.result.status == Status::Unhealthy);
+            let (code, status) = if failed {
+                (hyper::StatusCode::ServiceUnavailable, Status::Unhealthy)
+            } else {
+                (hyper::StatusCode::Ok, Status::Healthy)
+            };
+            response(code, &Report { status, checks })
+        }))
+    }
+}
+
+fn response(code: hyper::StatusCode, report: &Report) -> hyper::Response {
+    let body = serde_json::to_vec(report).expect("The serialization is infallible; qed");
+    hyper::Response::new()
+        .with_status(code)
+        .with_header(header::ContentType::json())
+        .with_header(header::ContentLength(body.len() as u64))
+        .with_body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use futures::{future, Future, Stream};
+    use hyper;
+    use serde_json;
+    use tokio_core::reactor::Core;
+
+    use router::Router;
+    use testing::TestClient;
+    use super::{Check, CheckResult, Health};
+
+    fn ready(health: &Health) -> (hyper::StatusCode, serde_json::Value) {
+        let response = Core::new().unwrap().run(health.ready()).unwrap();
+        let status = response.status();
+        let body = response.body().concat2().wait().unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn should_report_readiness() {
+        let health = Health::default();
+        health.check(Check::new("db", || Ok(CheckResult::healthy()) as Result<_, String>));
+        health.check(Check::new("cache", || Err("Connection refused.")).required(false));
+
+        let (status, report) = ready(&health);
+        assert_eq!(status, hyper::StatusCode::Ok);
+        assert_eq!(report["status"], json!("healthy"));
+        assert_eq!(report["checks"][0]["name"], json!("db"));
+        assert_eq!(report["checks"][1]["status"], json!("unhealthy"));
+        assert_eq!(report["checks"][1]["details"], json!("Connection refused."));
+
+        health.check(Check::new("queue", || future::empty::<CheckResult, String>()).timeout(Duration::from_millis(200)));
+        let (status, report) = ready(&health);
+        assert_eq!(status, hyper::StatusCode::ServiceUnavailable);
+        assert_eq!(report["status"], json!("unhealthy"));
+        assert_eq!(report["checks"][2]["details"], json!("Check timed out."));
+    }
+
+    #[test]
+    fn should_serve_health_endpoints() {
+        let mut router = Router::new();
+        router.health("/health")
+            .check(Check::new("db", || Ok(CheckResult::unhealthy("Down.")) as Result<_, String>));
+        let client = TestClient::new(router);
+
+        assert_eq!(client.get("/health/live").send().status(), hyper::StatusCode::Ok);
+        assert_eq!(client.get("/health/ready").send().status(), hyper::StatusCode::ServiceUnavailable);
+    }
+}
@@ -0,0 +1,283 @@
+//! JSON Web Token verification.
+//!
+//! Use `Validator::authenticator` to authenticate requests with JWT bearer tokens.
+//! Verified claims are available to handlers in request extensions.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use jsonwebtoken::{self, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use auth::{self, Authenticator};
+use error::Error;
+
+/// Default allowed clock skew.
+const DEFAULT_LEEWAY: Duration = Duration::from_secs(60);
+
+struct Key {
+    id: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// JWT validator deserializing verified claims into `T`.
+pub struct Validator<T> {
+    keys: Vec<Key>,
+    validation: Validation,
+    realm: String,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl<T> fmt::Debug for Validator<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let keys = self.keys.iter().map(|key| (&key.id, key.algorithm)).collect::<Vec<_>>();
+        fmt.debug_struct("Validator")
+            .field("keys", &keys)
+            .field("validation", &self.validation)
+            .finish()
+    }
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> Default for Validator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> Validator<T> {
+    /// Creates a validator without any keys.
+    ///
+    /// By default `exp` claim is required, `exp` and `nbf` are validated
+    /// and 60 seconds of clock skew is allowed.
+    pub fn new() -> Self {
+        let mut validation = Validation::default();
+        validation.validate_nbf = true;
+        validation.leeway = DEFAULT_LEEWAY.as_secs();
+        Validator {
+            keys: vec![],
+            validation,
+            realm: "api".into(),
+            _claims: PhantomData,
+        }
+    }
+
+    /// Accepts tokens signed with HS256 and given secret.
+    pub fn hs256(self, secret: &[u8]) -> Self {
+        self.key(None, Algorithm::HS256, DecodingKey::from_secret(secret))
+    }
+
+    /// Accepts tokens signed with RS256 and given public key (PEM).
+    pub fn rs256_pem(self, pem: &[u8]) -> io::Result<Self> {
+        let key = DecodingKey::from_rsa_pem(pem).map_err(invalid_key)?;
+        Ok(self.key(None, Algorithm::RS256, key))
+    }
+
+    /// Accepts tokens signed with ES256 and given public key (PEM).
+    pub fn es256_pem(self, pem: &[u8]) -> io::Result<Self> {
+        let key = DecodingKey::from_ec_pem(pem).map_err(invalid_key)?;
+        Ok(self.key(None, Algorithm::ES256, key))
+    }
+
+    /// Accepts tokens signed with keys from given JWKS file.
+    ///
+    /// Keys without `alg` parameter are used with HS256, RS256 or ES256
+    /// depending on the key type.
+    pub fn jwks_file<P: AsRef<Path>>(self, path: P) -> io::Result<Self> {
+        let content = fs::read(path)?;
+        self.jwks(&content)
+    }
+
+    /// Accepts tokens signed with keys from given JWKS document.
+    pub fn jwks(mut self, jwks: &[u8]) -> io::Result<Self> {
+        let set: JwkSet = serde_json::from_slice(jwks).map_err(invalid_key)?;
+        for jwk in &set.keys {
+            let algorithm = match (jwk.common.algorithm, &jwk.algorithm) {
+                (Some(algorithm), _) => algorithm,
+                (None, &AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+                (None, &AlgorithmParameters::EllipticCurve(_)) => Algorithm::ES256,
+                (None, &AlgorithmParameters::OctetKey(_)) => Algorithm::HS256,
+                (None, &AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+            };
+            let key = DecodingKey::from_jwk(jwk).map_err(invalid_key)?;
+            self = self.key(jwk.common.key_id.clone(), algorithm, key);
+        }
+        Ok(self)
+    }
+
+    fn key(mut self, id: Option<String>, algorithm: Algorithm, key: DecodingKey) -> Self {
+        self.keys.push(Key { id, algorithm, key });
+        self
+    }
+
+    /// Requires the `aud` claim to contain given audience.
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.validation.aud.get_or_insert_with(HashSet::new).insert(audience.into());
+        self
+    }
+
+    /// Requires the `iss` claim to be equal to given issuer.
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.validation.iss.get_or_insert_with(HashSet::new).insert(issuer.into());
+        self
+    }
+
+    /// Allowed clock skew for `exp` and `nbf` validation.
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.validation.leeway = leeway.as_secs();
+        self
+    }
+
+    /// Realm reported in `WWW-Authenticate` header.
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = realm.into();
+        self
+    }
+
+    /// Verifies the token and returns its claims.
+    ///
+    /// Returns `401` error if the token is malformed, has invalid signature or is expired
+    /// and `403` if the token is valid, but not meant for this service (audience, issuer).
+    pub fn verify(&self, token: &str) -> Result<T, Error> {
+        let header = jsonwebtoken::decode_header(token).map_err(|err| self.error(err.kind()))?;
+        let keys = self.keys.iter()
+            .filter(|key| key.algorithm == header.alg)
+            .filter(|key| match (header.kid.as_ref(), key.id.as_ref()) {
+                (Some(kid), Some(id)) => kid == id,
+                _ => true,
+            });
+
+        let mut result = Err(self.error(&ErrorKind::InvalidAlgorithm));
+        for key in keys {
+            let mut validation = self.validation.clone();
+            validation.algorithms = vec![key.algorithm];
+            match jsonwebtoken::decode::<T>(token, &key.key, &validation) {
+                Ok(data) => return Ok(data.claims),
+                Err(err) => {
+                    result = Err(self.error(err.kind()));
+                    // Try other keys only if the signature doesn't match.
+                    if *err.kind() != ErrorKind::InvalidSignature {
+                        break;
+                    }
+                },
+            }
+        }
+        result
+    }
+
+    /// Creates bearer authenticator storing verified claims in request extensions.
+    pub fn authenticator(self) -> Authenticator<T> {
+        let realm = self.realm.clone();
+        let validator = Arc::new(self);
+        Authenticator::bearer(&realm, move |token: String| validator.verify(&token).map(Some))
+    }
+
+    fn error(&self, kind: &ErrorKind) -> Error {
+        let (forbidden, error, details) = match *kind {
+            ErrorKind::ExpiredSignature => (false, "expired_token", "The token has expired.".into()),
+            ErrorKind::ImmatureSignature => (false, "immature_token", "The token is not valid yet.".into()),
+            ErrorKind::InvalidSignature => (false, "invalid_signature", "The token signature is invalid.".into()),
+            ErrorKind::InvalidAlgorithm => (false, "invalid_algorithm", "The token algorithm or key is not accepted.".into()),
+            ErrorKind::MissingRequiredClaim(ref claim) => (false, "missing_claim", format!("The token is missing `{}` claim.", claim)),
+            ErrorKind::InvalidAudience => (true, "invalid_audience", "The token is not meant for this audience.".into()),
+            ErrorKind::InvalidIssuer => (true, "invalid_issuer", "The token issuer is not trusted.".into()),
+            ErrorKind::InvalidSubject => (false, "invalid_subject", "The token subject is not accepted.".into()),
+            ErrorKind::MissingAlgorithm | ErrorKind::InvalidAlgorithmName =>
+                (false, "invalid_algorithm", "The token algorithm or key is not accepted.".into()),
+            ErrorKind::InvalidToken | ErrorKind::Base64(_) | ErrorKind::Json(_) | ErrorKind::Utf8(_) =>
+                (false, "invalid_token", "The token is malformed.".into()),
+            _ => (false, "invalid_token", "The token could not be verified.".into()),
+        };
+
+        let (mut err, challenge) = if forbidden {
+            (Error::forbidden(details.clone()), "insufficient_scope")
+        } else {
+            (Error::unauthorized(details.clone()), "invalid_token")
+        };
+        err.headers_mut().set_raw("WWW-Authenticate", format!(
+            "Bearer realm={}, error=\"{}\", error_description={}",
+            auth::quote(&self.realm), challenge, auth::quote(&details),
+        ));
+        err.with_data(json!({ "error": error }))
+    }
+}
+
+fn invalid_key<E: fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid key: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use hyper;
+    use super::Validator;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aud: Option<String>,
+    }
+
+    const FAR_FUTURE: u64 = 4_000_000_000;
+
+    fn token(secret: &[u8], kid: Option<&str>, claims: &Claims) -> String {
+        let header = Header { kid: kid.map(Into::into), ..Default::default() };
+        encode(&header, claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn claims(exp: u64, aud: Option<&str>) -> Claims {
+        Claims { sub: "alice".into(), exp, aud: aud.map(Into::into) }
+    }
+
+    #[test]
+    fn should_verify_hs256_tokens() {
+        let validator = Validator::<Claims>::new().hs256(b"secret").audience("shop");
+
+        let valid = claims(FAR_FUTURE, Some("shop"));
+        assert_eq!(validator.verify(&token(b"secret", None, &valid)).unwrap(), valid);
+
+        let error = validator.verify(&token(b"other", None, &valid)).unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+        assert_eq!(error.data, Some(json!({ "error": "invalid_signature" })));
+
+        let error = validator.verify(&token(b"secret", None, &claims(1, Some("shop")))).unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+        assert_eq!(error.data, Some(json!({ "error": "expired_token" })));
+
+        let error = validator.verify(&token(b"secret", None, &claims(FAR_FUTURE, Some("blog")))).unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Forbidden);
+        assert_eq!(error.data, Some(json!({ "error": "invalid_audience" })));
+
+        let error = validator.verify("not-a-token").unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::Unauthorized);
+        assert_eq!(
+            error.headers.unwrap().get_raw("WWW-Authenticate").unwrap(),
+            r#"Bearer realm="api", error="invalid_token", error_description="The token is malformed.""#
+        );
+    }
+
+    #[test]
+    fn should_load_keys_from_jwks_file() {
+        let path = ::std::env::temp_dir().join(format!("resty-jwks-{}.json", ::std::process::id()));
+        // `c2VjcmV0` is base64 of `secret`.
+        fs::write(&path, r#"{"keys":[{"kty":"oct","kid":"k1","k":"c2VjcmV0"},{"kty":"oct","kid":"k2","k":"b3RoZXJz"}]}"#).unwrap();
+        let validator = Validator::<Claims>::new().jwks_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let valid = claims(FAR_FUTURE, None);
+        assert!(validator.verify(&token(b"secret", Some("k1"), &valid)).is_ok());
+        assert!(validator.verify(&token(b"others", Some("k2"), &valid)).is_ok());
+        assert!(validator.verify(&token(b"secret", Some("k2"), &valid)).is_err());
+    }
+}
@@ -4,27 +4,66 @@
 
 extern crate arrayvec;
 extern crate futures;
+extern crate futures_cpupool;
 extern crate hyper;
+extern crate jsonwebtoken;
+extern crate mime_guess;
+extern crate net2;
+extern crate percent_encoding;
+extern crate regex;
+extern crate rustls;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
+extern crate serde_urlencoded;
+extern crate tokio_core;
+#[macro_use]
+extern crate tokio_io;
+extern crate tokio_timer;
+#[cfg(unix)]
+extern crate tokio_uds;
+#[cfg(test)]
+extern crate webpki;
 
 #[macro_use]
 extern crate serde_derive;
 
+pub mod access_log;
+pub mod auth;
+mod concurrency;
 mod config;
 mod error;
+pub mod extract;
+pub mod health;
+pub mod jwt;
+pub mod metrics;
+pub mod middleware;
+pub mod mock;
+pub mod openapi;
 mod prefix_tree;
+pub mod rate_limit;
 pub mod request;
 mod response;
 mod router;
 mod server;
+mod state;
+mod static_files;
+pub mod testing;
+mod timeout;
+pub mod tls;
+mod urls;
+pub mod validation;
 
+pub use access_log::AccessLog;
 pub use config::Config;
 pub use error::Error;
+pub use middleware::Middleware;
 pub use request::Request;
 pub use response::Response;
-pub use router::Router;
-pub use server::Listening;
+pub use router::{Conflict, DroppedRoute, ParamInfo, Route, RouteConfigInfo, RouteInfo, Router};
+pub use server::{Listening, Server, ServerConfig};
+pub use urls::{UrlError, UrlParams, Urls};
+pub use validation::Validate;
 pub use hyper::{Uri, StatusCode, Headers};
 
 #[cfg(test)]
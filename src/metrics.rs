@@ -0,0 +1,195 @@
+//! Prometheus request metrics.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use timeout;
+
+/// Upper bounds (in seconds) of latency histogram buckets.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Route label of requests not matching any route.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Labels of a single series.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+    method: String,
+    route: String,
+    status: &'static str,
+}
+
+/// Request counter and latency histogram of a single series.
+#[derive(Debug, Default)]
+struct Series {
+    count: u64,
+    sum: f64,
+    buckets: [u64; 11],
+}
+
+/// A registry of request metrics.
+///
+/// Collects request counters and latency histograms labeled
+/// by method, route template and status class.
+#[derive(Default)]
+pub struct Metrics {
+    series: Mutex<BTreeMap<Labels, Series>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Metrics")
+    }
+}
+
+impl Metrics {
+    /// Returns the number of requests being processed at the moment.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Returns the counter of in-flight requests.
+    pub(crate) fn in_flight_counter(&self) -> Arc<AtomicUsize> {
+        self.in_flight.clone()
+    }
+
+    /// Records a handled request.
+    pub fn observe(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let labels = Labels {
+            method: method.to_owned(),
+            route: route.to_owned(),
+            status: status_class(status),
+        };
+        let latency = timeout::secs(latency);
+
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let series = series.entry(labels).or_default();
+        series.count += 1;
+        series.sum += latency;
+        for (bucket, bound) in series.buckets.iter_mut().zip(BUCKETS.iter()) {
+            if latency <= *bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Renders all collected metrics in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Total number of handled HTTP requests.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for (labels, series) in series.iter() {
+            let _ = writeln!(out, "http_requests_total{{{}}} {}", labels, series.count);
+        }
+
+        out.push_str("# HELP http_requests_in_flight Number of HTTP requests being processed.\n");
+        out.push_str("# TYPE http_requests_in_flight gauge\n");
+        let _ = writeln!(out, "http_requests_in_flight {}", self.in_flight());
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency in seconds.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (labels, series) in series.iter() {
+            for (count, bound) in series.buckets.iter().zip(BUCKETS.iter()) {
+                let _ = writeln!(out, "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, count);
+            }
+            let _ = writeln!(out, "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, series.count);
+            let _ = writeln!(out, "http_request_duration_seconds_sum{{{}}} {}", labels, series.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count{{{}}} {}", labels, series.count);
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "method=\"{}\",route=\"{}\",status=\"{}\"",
+            escape(&self.method), escape(&self.route), self.status,
+        )
+    }
+}
+
+fn status_class(status: u16) -> &'static str {
+    match status / 100 {
+        1 => "1xx",
+        2 => "2xx",
+        3 => "3xx",
+        4 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Escapes label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use hyper::StatusCode;
+
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use testing::TestClient;
+    use super::Metrics;
+
+    #[test]
+    fn should_render_metrics() {
+        let metrics = Metrics::default();
+        metrics.observe("GET", "/products/{id}", 200, Duration::from_millis(20));
+        metrics.observe("GET", "/products/{id}", 204, Duration::from_millis(200));
+        metrics.observe("POST", "/products", 422, Duration::from_millis(1));
+
+        let rendered = metrics.render();
+        let labels = r#"method="GET",route="/products/{id}",status="2xx""#;
+        assert!(rendered.contains(&format!("http_requests_total{{{}}} 2\n", labels)));
+        assert!(rendered.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1\n", labels)));
+        assert!(rendered.contains(&format!("http_request_duration_seconds_bucket{{{},le=\"0.25\"}} 2\n", labels)));
+        assert!(rendered.contains(&format!("http_request_duration_seconds_count{{{}}} 2\n", labels)));
+        assert!(rendered.contains(r#"http_requests_total{method="POST",route="/products",status="4xx"} 1"#));
+    }
+
+    #[test]
+    fn should_collect_metrics_by_route_template() {
+        let mut products = Router::new();
+        products.get("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>);
+        let mut router = Router::new();
+        router.add("/products", products);
+        router.metrics("/metrics");
+        let client = TestClient::new(router);
+
+        for path in &["/products/1", "/products/2", "/products/3/x", "/unknown"] {
+            client.get(path).send();
+        }
+
+        let response = client.get("/metrics").send();
+        assert_eq!(response.status(), StatusCode::Ok);
+        let body = response.text();
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/products/{id}",status="2xx"} 2"#), "{}", body);
+        assert!(body.contains(r#"http_requests_total{method="GET",route="unmatched",status="4xx"} 2"#), "{}", body);
+        // The metrics request itself is being processed.
+        assert!(body.contains("http_requests_in_flight 1\n"), "{}", body);
+    }
+
+    #[test]
+    fn should_count_in_flight_requests_of_nested_router() {
+        let mut api = Router::new();
+        api.metrics("/metrics");
+        let mut router = Router::new();
+        router.add("/api", api);
+        let client = TestClient::new(router);
+
+        let body = client.get("/api/metrics").send().text();
+        assert!(body.contains("http_requests_in_flight 1\n"), "{}", body);
+    }
+}
@@ -0,0 +1,95 @@
+//! Request processing middlewares.
+
+use std::fmt;
+use std::sync::Arc;
+use futures::{future, Future};
+use hyper;
+
+use error::Error;
+use request::{Extensions, Request};
+
+/// Result of processing the request by middleware.
+pub type MiddlewareResult = Box<Future<Item = Request, Error = Error>>;
+
+/// Request processing middleware.
+///
+/// Middlewares are invoked (in the order of registration) before the handler
+/// and can modify the request (i.e. insert extensions) or reject it with an error.
+pub trait Middleware: Send + Sync + 'static {
+    /// Process the request before it reaches the handler.
+    fn on_request(&self, request: Request) -> MiddlewareResult {
+        Box::new(future::ok(request))
+    }
+
+    /// Process the response before it's returned.
+    /// Extensions contain values inserted before the handler was invoked.
+    fn on_response(&self, response: hyper::Response, _extensions: &Extensions) -> hyper::Response {
+        response
+    }
+}
+
+/// A list of middlewares.
+#[derive(Clone, Default)]
+pub(crate) struct Middlewares(pub Vec<Arc<Middleware>>);
+
+impl fmt::Debug for Middlewares {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Middlewares({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use hyper::{self, header, StatusCode};
+
+    use config::Config;
+    use error::Error;
+    use request::{Extensions, Request};
+    use router::Router;
+    use testing::TestClient;
+    use super::{Middleware, MiddlewareResult};
+
+    #[derive(Debug, Clone)]
+    struct User(String);
+
+    struct Auth;
+    impl Middleware for Auth {
+        fn on_request(&self, mut request: Request) -> MiddlewareResult {
+            let user = request.headers().get::<header::Authorization<String>>().map(|auth| auth.0.clone());
+            match user {
+                Some(user) => {
+                    request.extensions_mut().insert(User(user));
+                    Box::new(future::ok(request))
+                },
+                None => Box::new(future::err(Error::bad_request("Missing user.", ""))),
+            }
+        }
+
+        fn on_response(&self, mut response: hyper::Response, extensions: &Extensions) -> hyper::Response {
+            if let Some(user) = extensions.get::<User>() {
+                response.headers_mut().set_raw("X-User", user.0.clone());
+            }
+            response
+        }
+    }
+
+    #[test]
+    fn should_pass_extensions_from_middleware() {
+        let mut router = Router::with_config(Config::new().middleware(Auth));
+        router.get("/hello", |request: Request<_>| {
+            let user = request.extensions().get::<User>().cloned();
+            Ok(format!("Hello {}!", user.unwrap().0)) as Result<_, Error>
+        });
+        let client = TestClient::new(router);
+
+        let response = client.get("/hello").header("Authorization", "alice").send();
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.header("X-User"), Some("alice"));
+        assert_eq!(response.text(), "\"Hello alice!\"");
+
+        let response = client.get("/hello").send();
+        assert_eq!(response.status(), StatusCode::BadRequest);
+        assert!(response.header("X-User").is_none());
+    }
+}
@@ -0,0 +1,515 @@
+//! Mock HTTP server for testing API clients.
+//!
+//! Expectations are matched using the same routing as `Router`,
+//! so paths may contain parameters (i.e. `/v1/products/{id}`).
+//!
+//! ```rust,ignore
+//! let mock = MockServer::start();
+//! mock.expect(Expectation::get("/v1/products/{id}")
+//!     .header("Authorization", "Bearer token")
+//!     .times(1)
+//!     .returns(&Product { id: 5 }));
+//!
+//! let product = client(mock.url()).product(5);
+//! // Unmet expectations and unmatched requests are reported (with a panic) when the server is dropped.
+//! ```
+
+use std::fmt;
+use std::io;
+use std::net::{self, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use futures::{Future, Stream};
+use hyper::{self, header};
+use hyper::server::Service;
+use serde::Serialize;
+use serde_json;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Handle;
+
+use config::Config;
+use error::Error;
+use request::params::DynamicParams;
+use request::{Params, Request};
+use response::Response;
+use router::{Method, Router, MAX_NUMBER_OF_ENDPOINTS};
+use server::{Listening, Server};
+
+/// Header marking responses stubbed by an expectation (removed before the response is sent).
+const MATCHED_HEADER: &str = "X-Mock-Matched";
+
+/// Expected request and the response returned for it.
+#[derive(Debug)]
+pub struct Expectation {
+    method: hyper::Method,
+    path: String,
+    headers: Vec<(String, String)>,
+    times: Option<usize>,
+    exact: bool,
+    status: hyper::StatusCode,
+    body: Vec<u8>,
+    hits: AtomicUsize,
+}
+
+impl Expectation {
+    /// Expects a request with given method and path (may contain parameters).
+    ///
+    /// By default the expectation has to be met at least once and returns `200` with empty JSON object.
+    ///
+    /// # Panics
+    ///
+    /// If the method is not supported by `Router` (i.e. `PURGE`).
+    pub fn new(method: hyper::Method, path: &str) -> Self {
+        assert!(route_method(&method).is_some(), "Method {} is not supported by the mock server.", method);
+        Expectation {
+            method,
+            path: path.into(),
+            headers: vec![],
+            times: None,
+            exact: false,
+            status: hyper::StatusCode::Ok,
+            body: b"{}".to_vec(),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Expects GET request.
+    pub fn get(path: &str) -> Self {
+        Self::new(hyper::Method::Get, path)
+    }
+
+    /// Expects POST request.
+    pub fn post(path: &str) -> Self {
+        Self::new(hyper::Method::Post, path)
+    }
+
+    /// Expects PUT request.
+    pub fn put(path: &str) -> Self {
+        Self::new(hyper::Method::Put, path)
+    }
+
+    /// Expects PATCH request.
+    pub fn patch(path: &str) -> Self {
+        Self::new(hyper::Method::Patch, path)
+    }
+
+    /// Expects DELETE request.
+    pub fn delete(path: &str) -> Self {
+        Self::new(hyper::Method::Delete, path)
+    }
+
+    /// Requires the request to contain given header value.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Expects exactly `times` matching requests.
+    ///
+    /// Requests over the limit are not matched by this expectation.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self.exact = true;
+        self
+    }
+
+    /// Allows at most `times` matching requests (the expectation may not be met at all).
+    pub fn at_most(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self.exact = false;
+        self
+    }
+
+    /// Status of the response (defaults to `200`).
+    pub fn status(mut self, status: hyper::StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Body of the response serialized to JSON.
+    pub fn returns<T: Serialize>(mut self, body: &T) -> Self {
+        self.body = serde_json::to_vec(body).expect("Unable to serialize response body.");
+        self
+    }
+
+    /// Returns the number of requests matched by this expectation.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    fn matches(&self, request: &Request<DynamicParams>) -> bool {
+        self.headers.iter().all(|(name, value)| {
+            request.headers().get_raw(name).into_iter()
+                .flat_map(|raw| raw.iter())
+                .any(|v| v == value.as_bytes())
+        })
+    }
+
+    /// Registers a hit unless the limit has been reached.
+    fn hit(&self) -> bool {
+        let limit = self.times.unwrap_or(usize::MAX);
+        let mut hits = self.hits.load(Ordering::SeqCst);
+        while hits < limit {
+            match self.hits.compare_exchange(hits, hits + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return true,
+                Err(current) => hits = current,
+            }
+        }
+        false
+    }
+
+    fn response(&self) -> Response {
+        let mut response = hyper::Response::new()
+            .with_status(self.status)
+            .with_header(header::ContentType::json())
+            .with_header(header::ContentLength(self.body.len() as u64))
+            .with_body(self.body.clone());
+        response.headers_mut().set_raw(MATCHED_HEADER, "1");
+        Response::raw(response)
+    }
+
+    /// Describes the expectation if it's not met.
+    fn unmet(&self) -> Option<String> {
+        let hits = self.hits();
+        let expected = match self.times {
+            Some(times) if self.exact && hits != times => format!("exactly {}", times),
+            None if hits == 0 => "at least 1".into(),
+            _ => return None,
+        };
+        Some(format!("{} {}: expected {} request(s), received {}", self.method, self.path, expected, hits))
+    }
+}
+
+/// Returns the method of `Router` routes matching given method.
+fn route_method(method: &hyper::Method) -> Option<Method> {
+    match *method {
+        hyper::Method::Head => Some(Method::Head),
+        hyper::Method::Get => Some(Method::Get),
+        hyper::Method::Post => Some(Method::Post),
+        hyper::Method::Patch => Some(Method::Patch),
+        hyper::Method::Delete => Some(Method::Delete),
+        hyper::Method::Put => Some(Method::Put),
+        hyper::Method::Options => Some(Method::Options),
+        _ => None,
+    }
+}
+
+/// Returns the prefix of the path, routes with the same prefix are handled by a single endpoint.
+fn prefix(path: &str) -> &str {
+    let params: Params = path.into();
+    params.prefix
+}
+
+/// Request received by the mock server.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Request method.
+    pub method: hyper::Method,
+    /// Request path (including query).
+    pub path: String,
+    /// Request headers.
+    pub headers: hyper::Headers,
+    /// Request body.
+    pub body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    expectations: Vec<Arc<Expectation>>,
+    requests: Vec<RecordedRequest>,
+    unmatched: Vec<String>,
+}
+
+/// HTTP server returning stubbed responses for expected requests.
+///
+/// Requests not matching any expectation get `404`.
+/// Unmet expectations and unmatched requests cause a panic when the server is dropped (see `verify`).
+pub struct MockServer {
+    address: SocketAddr,
+    state: Arc<Mutex<State>>,
+    server: Arc<RwLock<Server>>,
+    listening: Option<Listening>,
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MockServer")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+impl MockServer {
+    /// Starts the server on an ephemeral port of the loopback interface.
+    pub fn start() -> Self {
+        Self::try_start().expect("Unable to start mock server.")
+    }
+
+    fn try_start() -> io::Result<Self> {
+        let listener = net::TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let server = Arc::new(RwLock::new(Router::new().into_service()));
+        let service = MockService {
+            state: state.clone(),
+            server: server.clone(),
+        };
+
+        let listening = Listening::spawn(Some(address), vec![move |handle: &Handle| {
+            let listener = TcpListener::from_listener(listener, &address, handle)?;
+            let http = hyper::server::Http::<hyper::Chunk>::new();
+            let handle = handle.clone();
+            Ok(Box::new(listener.incoming().for_each(move |(io, address)| {
+                #[allow(deprecated)]
+                http.bind_connection(&handle, io, address, service.clone());
+                Ok(())
+            })) as Box<Future<Item = (), Error = io::Error>>)
+        }])?;
+
+        Ok(MockServer {
+            address,
+            state,
+            server,
+            listening: Some(listening),
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Returns base URL of the server (i.e. `http://127.0.0.1:1234`).
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Registers an expectation and returns a handle to it.
+    ///
+    /// Expectations are matched in the order of registration.
+    ///
+    /// # Panics
+    ///
+    /// If there are more than 6 distinct routes (method and path) sharing the same prefix
+    /// (the part of the path before the first parameter), which is the limit of `Router`.
+    pub fn expect(&self, expectation: Expectation) -> Arc<Expectation> {
+        let expectation = Arc::new(expectation);
+        let mut state = self.state.lock().expect("Lock is never poisoned; qed");
+        let mut expectations = state.expectations.clone();
+        expectations.push(expectation.clone());
+
+        // Expectations for the same route are served by a single handler.
+        let mut routes: Vec<(&hyper::Method, &str, Vec<Arc<Expectation>>)> = vec![];
+        for expectation in &expectations {
+            match routes.iter_mut().find(|r| *r.0 == expectation.method && r.1 == expectation.path) {
+                Some(route) => route.2.push(expectation.clone()),
+                None => routes.push((&expectation.method, &expectation.path, vec![expectation.clone()])),
+            }
+        }
+        let prefix = prefix(&expectation.path);
+        let shared = routes.iter().filter(|route| self::prefix(route.1) == prefix).count();
+        if shared > MAX_NUMBER_OF_ENDPOINTS {
+            // Release the lock first, so that the server can still be verified.
+            drop(state);
+            panic!(
+                "The mock server does not support more than {} routes with prefix {:?} (got {} for {} {}).",
+                MAX_NUMBER_OF_ENDPOINTS, prefix, shared, expectation.method, expectation.path,
+            );
+        }
+
+        let mut router = Router::with_config(Config::new().handle_head(false).handle_options(false));
+        for (method, path, expectations) in routes {
+            let method = route_method(method).expect("Methods are validated when creating expectations; qed");
+            router.on(method, path, move |request: Request<DynamicParams>| {
+                expectations.iter()
+                    .find(|expectation| expectation.matches(&request) && expectation.hit())
+                    .map(|expectation| expectation.response())
+                    .ok_or_else(|| Error::not_found("No matching expectation."))
+            });
+        }
+        *self.server.write().expect("Lock is never poisoned; qed") = router.into_service();
+        state.expectations = expectations;
+
+        expectation
+    }
+
+    /// Returns all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().expect("Lock is never poisoned; qed").requests.clone()
+    }
+
+    /// Checks that all expectations are met and every request matched one of them.
+    pub fn verify(&self) -> Result<(), String> {
+        let state = self.state.lock().expect("Lock is never poisoned; qed");
+        let unmet = state.expectations.iter().filter_map(|e| e.unmet()).collect::<Vec<_>>();
+        let mut errors = vec![];
+        if !unmet.is_empty() {
+            errors.push(format!("Unmet expectations:\n  {}", unmet.join("\n  ")));
+        }
+        if !state.unmatched.is_empty() {
+            errors.push(format!("Unmatched requests:\n  {}", state.unmatched.join("\n  ")));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // Make sure all requests have been processed.
+        self.listening.take();
+        if thread::panicking() {
+            return;
+        }
+        if let Err(err) = self.verify() {
+            panic!("{}", err);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MockService {
+    state: Arc<Mutex<State>>,
+    server: Arc<RwLock<Server>>,
+}
+
+impl Service for MockService {
+    type Request = hyper::Request;
+    type Response = hyper::Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = hyper::Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let (method, uri, version, headers, body) = req.deconstruct();
+        let (state, server) = (self.state.clone(), self.server.clone());
+        Box::new(body.concat2().and_then(move |body| {
+            let body = body.to_vec();
+            let description = format!("{} {}", method, uri);
+            state.lock().expect("Lock is never poisoned; qed").requests.push(RecordedRequest {
+                method: method.clone(),
+                path: uri.as_ref().to_owned(),
+                headers: headers.clone(),
+                body: body.clone(),
+            });
+
+            let mut request = hyper::Request::new(method, uri);
+            request.set_version(version);
+            *request.headers_mut() = headers;
+            request.set_body(body);
+            let server = server.read().expect("Lock is never poisoned; qed").clone();
+            server.call(request).map(move |mut response| {
+                if response.headers().get_raw(MATCHED_HEADER).is_some() {
+                    response.headers_mut().remove_raw(MATCHED_HEADER);
+                } else {
+                    state.lock().expect("Lock is never poisoned; qed").unmatched.push(description);
+                }
+                response
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::panic;
+    use hyper;
+
+    use super::{Expectation, MockServer};
+
+    fn get(address: &SocketAddr, path: &str, headers: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, headers).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn should_return_stubbed_responses() {
+        let mock = MockServer::start();
+        let expectation = mock.expect(Expectation::get("/v1/products/{id}")
+            .header("Authorization", "Bearer token")
+            .times(1)
+            .returns(&json!({"id": 5})));
+
+        let response = get(mock.addr(), "/v1/products/5", "Authorization: Bearer token\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with(r#"{"id":5}"#), "{}", response);
+        // Over the limit.
+        let response = get(mock.addr(), "/v1/products/5", "Authorization: Bearer token\r\n");
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        // Missing header.
+        let response = get(mock.addr(), "/v1/products/6", "");
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        assert_eq!(expectation.hits(), 1);
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].method, hyper::Method::Get);
+        assert_eq!(requests[2].path, "/v1/products/6");
+        assert_eq!(
+            mock.verify(),
+            Err("Unmatched requests:\n  GET /v1/products/5\n  GET /v1/products/6".into())
+        );
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(move || drop(mock)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_report_requests_without_route() {
+        let mock = MockServer::start();
+        mock.expect(Expectation::get("/v1/products"));
+
+        let response = get(mock.addr(), "/v1/products", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(!response.contains("X-Mock-Matched"), "{}", response);
+        let response = get(mock.addr(), "/v1/orders", "");
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        assert_eq!(mock.verify(), Err("Unmatched requests:\n  GET /v1/orders".into()));
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(move || drop(mock)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_report_unmet_expectations() {
+        let mock = MockServer::start();
+        mock.expect(Expectation::post("/v1/products").times(2));
+        mock.expect(Expectation::get("/v1/products").at_most(1));
+        assert_eq!(
+            mock.verify(),
+            Err("Unmet expectations:\n  POST /v1/products: expected exactly 2 request(s), received 0".into())
+        );
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(move || drop(mock)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Method PURGE is not supported by the mock server.")]
+    fn should_reject_unsupported_methods() {
+        Expectation::new(hyper::Method::Extension("PURGE".into()), "/v1/products");
+    }
+
+    #[test]
+    fn should_limit_routes_sharing_prefix() {
+        let mock = MockServer::start();
+        for i in 0..6 {
+            mock.expect(Expectation::get(&format!("/v1/products/{{id}}/{}", i)).at_most(1));
+        }
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| mock.expect(Expectation::post("/v1/products/{id}"))));
+        let error = result.unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with(r#"The mock server does not support more than 6 routes with prefix "/v1/products/""#), "{}", message);
+        // Other prefixes are not affected.
+        mock.expect(Expectation::get("/v1/orders").at_most(1));
+        assert_eq!(mock.verify(), Ok(()));
+    }
+}
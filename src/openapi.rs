@@ -0,0 +1,233 @@
+//! OpenAPI 3 documentation of the routes.
+//!
+//! Path parameters are documented automatically (with types taken from `url!` declarations),
+//! request and response bodies need to be declared on the route:
+//!
+//! ```rust,ignore
+//! impl Schema for Product {
+//!     fn schema() -> serde_json::Value {
+//!         json!({
+//!             "type": "object",
+//!             "properties": { "id": u64::schema(), "name": String::schema() },
+//!         })
+//!     }
+//! }
+//!
+//! router.put(url!(/{id:u64}), handler)
+//!     .request_schema::<Product>()
+//!     .response_schema::<Product>();
+//! router.openapi_route("/openapi.json", "Products", "1.0.0");
+//! ```
+
+use std::collections::BTreeMap;
+use serde_json::{self, Value};
+
+/// JSON Schema of a request or response body.
+pub trait Schema {
+    /// Returns JSON Schema describing the type.
+    fn schema() -> Value;
+}
+
+macro_rules! impl_schema {
+    ($($t:ty),* => $schema:expr) => {
+        $(
+            impl Schema for $t {
+                fn schema() -> Value {
+                    $schema
+                }
+            }
+        )*
+    };
+}
+
+impl_schema!(u8, u16, i8, i16, i32 => json!({ "type": "integer", "format": "int32" }));
+// `u32` does not fit into (signed) `int32`.
+impl_schema!(u32, u64, usize, i64, isize => json!({ "type": "integer", "format": "int64" }));
+impl_schema!(f32 => json!({ "type": "number", "format": "float" }));
+impl_schema!(f64 => json!({ "type": "number", "format": "double" }));
+impl_schema!(bool => json!({ "type": "boolean" }));
+impl_schema!(String, str => json!({ "type": "string" }));
+impl_schema!(Value => json!({}));
+
+impl<T: Schema + ?Sized> Schema for &T {
+    fn schema() -> Value {
+        T::schema()
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn schema() -> Value {
+        let mut schema = T::schema();
+        if let Value::Object(ref mut map) = schema {
+            map.insert("nullable".into(), Value::Bool(true));
+        }
+        schema
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": T::schema() })
+    }
+}
+
+impl<T: Schema> Schema for BTreeMap<String, T> {
+    fn schema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::schema() })
+    }
+}
+
+/// Returns the schema of path parameter declared with given Rust type.
+pub(crate) fn param_schema(rust_type: &str) -> Value {
+    match rust_type {
+        "u8" | "u16" | "i8" | "i16" | "i32" => i32::schema(),
+        "u32" | "u64" | "usize" | "i64" | "isize" => u64::schema(),
+        "f32" => f32::schema(),
+        "f64" => f64::schema(),
+        "bool" => bool::schema(),
+        _ => String::schema(),
+    }
+}
+
+/// Returns names of the parameters in given route pattern (i.e. `/products/{id}`).
+pub(crate) fn pattern_params(pattern: &str) -> Vec<String> {
+    pattern.split('/')
+        .filter(|segment| segment.len() > 1 && segment.starts_with('{') && segment.ends_with('}'))
+        .map(|segment| segment[1..segment.len() - 1].trim_start_matches('*').to_owned())
+        .collect()
+}
+
+/// Returns names and Rust types (`String` if not declared) of the parameters in given route pattern.
+pub(crate) fn pattern_param_types<'a>(pattern: &str, param_types: &'a [(String, String)]) -> Vec<(String, &'a str)> {
+    pattern_params(pattern).into_iter().map(|name| {
+        let param_type = param_types.iter()
+            .find(|(param, _)| *param == name)
+            .map_or("String", |(_, param_type)| param_type.as_str());
+        (name, param_type)
+    }).collect()
+}
+
+/// Converts route pattern to OpenAPI path template (catch-all `{*path}` becomes `{path}`).
+fn path_template(pattern: &str) -> String {
+    pattern.replace("{*", "{")
+}
+
+/// Documentation of a single route.
+pub(crate) struct Operation<'a> {
+    pub method: String,
+    pub pattern: String,
+    pub param_types: &'a [(String, String)],
+    pub request: Option<&'a Value>,
+    pub response: Option<&'a Value>,
+    pub permissions: Vec<String>,
+}
+
+/// Builds OpenAPI document describing given operations.
+pub(crate) fn document<'a, I: IntoIterator<Item = Operation<'a>>>(title: &str, version: &str, operations: I) -> Value {
+    let mut paths = serde_json::Map::new();
+    for op in operations {
+        let parameters = pattern_param_types(&op.pattern, op.param_types).into_iter().map(|(name, rust_type)| {
+            json!({ "name": name, "in": "path", "required": true, "schema": param_schema(rust_type) })
+        }).collect::<Vec<_>>();
+
+        let mut response = json!({ "description": "Success" });
+        if let Some(schema) = op.response {
+            response["content"] = json!({ "application/json": { "schema": schema } });
+        }
+        let mut operation = json!({ "responses": { "200": response } });
+        if !parameters.is_empty() {
+            operation["parameters"] = Value::Array(parameters);
+        }
+        if let Some(schema) = op.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            });
+        }
+        if !op.permissions.is_empty() {
+            operation["x-required-permissions"] = json!(op.permissions);
+        }
+
+        let path = paths.entry(path_template(&op.pattern)).or_insert_with(|| json!({}));
+        path[op.method.to_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.0",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use testing::TestClient;
+    use super::{document, param_schema, pattern_param_types, pattern_params, Operation, Schema};
+
+    #[test]
+    fn should_describe_types() {
+        assert_eq!(Vec::<Option<u8>>::schema(), json!({
+            "type": "array",
+            "items": { "type": "integer", "format": "int32", "nullable": true },
+        }));
+        assert_eq!(u32::schema(), json!({ "type": "integer", "format": "int64" }));
+        assert_eq!(param_schema("u32"), u32::schema());
+        assert_eq!(pattern_params("/v1/products/{id}/tags/{*tag}"), vec!["id".to_owned(), "tag".to_owned()]);
+        let types = vec![("id".to_owned(), "u32".to_owned())];
+        assert_eq!(
+            pattern_param_types("/v1/products/{id}/tags/{*tag}", &types),
+            vec![("id".to_owned(), "u32"), ("tag".to_owned(), "String")]
+        );
+    }
+
+    #[test]
+    fn should_document_catch_all_routes() {
+        let operation = Operation {
+            method: "GET".into(),
+            pattern: "/static/{*path}".into(),
+            param_types: &[],
+            request: None,
+            response: None,
+            permissions: vec![],
+        };
+        let document = document("Files", "1.0", vec![operation]);
+
+        assert_eq!(document["paths"]["/static/{path}"]["get"]["parameters"], json!([
+            { "name": "path", "in": "path", "required": true, "schema": { "type": "string" } },
+        ]));
+        assert!(document["paths"].get("/static/{*path}").is_none());
+    }
+
+    #[test]
+    fn should_generate_openapi_document() {
+        use {parser, printer, url, url_internal};
+
+        let mut products = Router::new();
+        products.get(url!(/{id:u64}), |request: Request<_>| Ok(request.params().id) as Result<_, Error>)
+            .response_schema::<String>();
+        products.put("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>)
+            .request_schema::<Vec<String>>();
+        let mut router = Router::new();
+        router.add("/v1/products", products);
+        router.openapi_route("/openapi.json", "Products", "1.0.0");
+        let client = TestClient::new(router);
+
+        let document = client.get("/openapi.json").send().json::<Value>().unwrap();
+        assert_eq!(document["info"], json!({ "title": "Products", "version": "1.0.0" }));
+        let path = &document["paths"]["/v1/products/{id}"];
+        assert_eq!(path["get"]["parameters"], json!([{
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "schema": { "type": "integer", "format": "int64" },
+        }]));
+        assert_eq!(path["get"]["responses"]["200"]["content"]["application/json"]["schema"], json!({ "type": "string" }));
+        assert_eq!(path["put"]["parameters"][0]["schema"], json!({ "type": "string" }));
+        assert_eq!(path["put"]["requestBody"]["content"]["application/json"]["schema"]["type"], "array");
+    }
+}
@@ -2,10 +2,11 @@ use std::mem;
 use std::fmt;
 use arrayvec::ArrayVec;
 
+// Data is boxed to keep the node (and the whole tree level) small.
 enum Node<T> {
     Empty,
-    Data(T),
-    Tree(Option<T>, Box<Tree<T>>),
+    Data(Box<T>),
+    Tree(Option<Box<T>>, Box<Tree<T>>),
 }
 
 const SIZE: usize = 256;
@@ -42,25 +43,39 @@ impl<T> Default for Tree<T> {
     }
 }
 
-fn merge_nodes<T>(me: Node<T>, other: Node<T>) -> Node<T> {
+/// Resolves a collision of two elements stored under the same key while merging trees.
+/// Receives the key, the existing element and the merged one, returns the element to keep.
+pub type Resolve<'a, T> = FnMut(&[u8], Box<T>, Box<T>) -> Box<T> + 'a;
+
+fn merge_data<T>(key: &[u8], me: Option<Box<T>>, other: Option<Box<T>>, resolve: &mut Resolve<T>) -> Option<Box<T>> {
+    match (me, other) {
+        (Some(a), Some(b)) => Some(resolve(key, a, b)),
+        (a, b) => b.or(a),
+    }
+}
+
+fn merge_nodes<T>(me: Node<T>, other: Node<T>, key: &mut Vec<u8>, resolve: &mut Resolve<T>) -> Node<T> {
     match (me, other) {
         (Node::Empty, any) => any,
         (any, Node::Empty) => any,
-        (Node::Data(_), Node::Data(d)) => Node::Data(d),
-        (Node::Data(a), Node::Tree(b, next)) => Node::Tree(b.or(Some(a)), next),
-        (Node::Tree(_, next), Node::Data(d)) => Node::Tree(Some(d), next),
+        (Node::Data(a), Node::Data(b)) => Node::Data(resolve(key, a, b)),
+        (Node::Data(a), Node::Tree(b, next)) => Node::Tree(merge_data(key, Some(a), b, resolve), next),
+        (Node::Tree(a, next), Node::Data(b)) => Node::Tree(merge_data(key, a, Some(b), resolve), next),
         (Node::Tree(a, mut next), Node::Tree(b, mut next2)) => {
-            merge_trees(&mut next, &mut next2);
-            Node::Tree(b.or(a), next)
+            let data = merge_data(key, a, b, resolve);
+            merge_trees(&mut next, &mut next2, key, resolve);
+            Node::Tree(data, next)
         },
     }
 }
 
-fn merge_trees<T>(me: &mut Tree<T>, other: &mut Tree<T>) {
+fn merge_trees<T>(me: &mut Tree<T>, other: &mut Tree<T>, key: &mut Vec<u8>, resolve: &mut Resolve<T>) {
     for i in 0..SIZE {
         let old_me = mem::replace(&mut me.routes[i], Node::Empty);
         let old_other = mem::replace(&mut other.routes[i], Node::Empty);
-        me.routes[i] = merge_nodes(old_me, old_other);
+        key.push(i as u8);
+        me.routes[i] = merge_nodes(old_me, old_other, key, resolve);
+        key.pop();
     }
 }
 
@@ -86,10 +101,12 @@ impl<T> Tree<T> {
         }
     }
 
-    pub fn merge<K: AsRef<[u8]>>(&mut self, prefix: K, mut other: Tree<T>) {
+    /// Merges other tree under given prefix resolving collisions with given function.
+    pub fn merge_with<K: AsRef<[u8]>>(&mut self, prefix: K, mut other: Tree<T>, resolve: &mut Resolve<T>) {
         let bytes = prefix.as_ref();
+        let mut key = bytes.to_vec();
         if bytes.is_empty() {
-            merge_trees(self, &mut other);
+            merge_trees(self, &mut other, &mut key, resolve);
             return;
         }
 
@@ -108,7 +125,7 @@ impl<T> Tree<T> {
 
             if let Node::Tree(_, ref mut tree) = current[b] {
                 if is_last {
-                    merge_trees(tree, &mut other);
+                    merge_trees(tree, &mut other, &mut key, resolve);
                     return;
                 }
                 next = &mut tree.routes as *mut [Node<T>; SIZE];
@@ -138,7 +155,7 @@ impl<T> Tree<T> {
                     Node::Tree(d, tree) => (Node::Tree(None, tree), d),
                 };
                 current[b] = new;
-                return old;
+                return old.map(|d| *d);
             }
 
             current[b] = match old {
@@ -162,6 +179,7 @@ impl<T> Tree<T> {
         assert!(len > 0, "Empty keys are not supported.");
 
         let mut pos = 0;
+        let value = Box::new(value);
         let mut next = &mut self.routes as *mut [Node<T>; SIZE];
         loop {
             let is_last = pos == len - 1;
@@ -175,7 +193,7 @@ impl<T> Tree<T> {
                     Node::Tree(d, tree) => (Node::Tree(Some(value), tree), d),
                 };
                 current[b] = new;
-                return old;
+                return old.map(|d| *d);
             }
 
             current[b] = match old {
@@ -193,6 +211,29 @@ impl<T> Tree<T> {
         }
     }
 
+    /// Returns a mutable reference to the element stored exactly under given key.
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut T> {
+        let bytes = key.as_ref();
+        let len = bytes.len();
+
+        let mut current = &mut self.routes;
+        for (pos, byte) in bytes.iter().enumerate() {
+            let is_last = pos == len - 1;
+            match current[*byte as usize] {
+                Node::Empty => return None,
+                Node::Data(ref mut t) => return if is_last { Some(&mut **t) } else { None },
+                Node::Tree(ref mut d, ref mut tree) => {
+                    if is_last {
+                        return d.as_mut().map(|t| &mut **t);
+                    }
+                    current = &mut tree.routes;
+                },
+            }
+        }
+
+        None
+    }
+
     /// Finds the first terminal element by looking at the prefix.
     /// Returns the length of the matched prefix and a reference to the element.
     pub fn find<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &T)> {
@@ -205,11 +246,11 @@ impl<T> Tree<T> {
                 // final node
                 Node::Empty => return best_result,
                 // Save it as best result, but look for longer pattern
-                Node::Data(ref t) => best_result = Some((pos + 1, t)),
+                Node::Data(ref t) => best_result = Some((pos + 1, &**t)),
                 // Descend in the tree
                 Node::Tree(ref top_level, ref tree) => {
                     if let Some(ref top_level) = *top_level {
-                        best_result = Some((pos + 1, top_level));
+                        best_result = Some((pos + 1, &**top_level));
                     }
                     current = &tree.routes
                 },
@@ -241,8 +282,8 @@ impl<'a, T: 'a> Iterator for TreeIterator<'a, T> {
 
             let (display, next) = match tree.routes[next_pos] {
                 Node::Empty => (None, None),
-                Node::Data(ref t) => (Some((current_prefix, t)), None),
-                Node::Tree(ref d, ref tree) => (d.as_ref().map(|t| (current_prefix, t)), Some(tree)),
+                Node::Data(ref t) => (Some((current_prefix, &**t)), None),
+                Node::Tree(ref d, ref tree) => (d.as_ref().map(|t| (current_prefix, &**t)), Some(tree)),
             };
 
             if next_pos + 1 < SIZE {
@@ -284,8 +325,8 @@ impl<'a, T: 'a> Iterator for TreeIteratorMut<'a, T> {
 
             let (display, next) = match unsafe { &mut *tree }.routes[next_pos] {
                 Node::Empty => (None, None),
-                Node::Data(ref mut t) => (Some((current_prefix, t)), None),
-                Node::Tree(ref mut d, ref mut tree) => (d.as_mut().map(|t| (current_prefix, t)), Some(tree)),
+                Node::Data(ref mut t) => (Some((current_prefix, &mut **t)), None),
+                Node::Tree(ref mut d, ref mut tree) => (d.as_mut().map(|t| (current_prefix, &mut **t)), Some(tree)),
             };
 
             if next_pos + 1 < SIZE {
@@ -332,7 +373,7 @@ mod tests {
         tree2.insert("abc", 7);
         tree2.insert("xyz", 10);
 
-        tree1.merge("a", tree2);
+        tree1.merge_with("a", tree2, &mut |_, _, b| b);
 
         assert_eq!(tree1.find("ab"), Some((2, &5)));
         assert_eq!(tree1.find("abc"), Some((3, &4)));
@@ -344,6 +385,28 @@ mod tests {
         assert_eq!(tree1.find("z"), Some((1, &6)));
     }
 
+    #[test]
+    fn should_resolve_collisions() {
+        let mut tree1 = Tree::new();
+        tree1.insert("ab", 1);
+        tree1.insert("abc", 2);
+        let mut tree2 = Tree::new();
+        tree2.insert("b", 3);
+        tree2.insert("bc", 4);
+        tree2.insert("bd", 5);
+
+        let mut collisions = vec![];
+        tree1.merge_with("a", tree2, &mut |key, a, b| {
+            collisions.push((key.to_vec(), *a, *b));
+            a
+        });
+
+        assert_eq!(collisions, vec![(b"ab".to_vec(), 1, 3), (b"abc".to_vec(), 2, 4)]);
+        assert_eq!(tree1.find("ab"), Some((2, &1)));
+        assert_eq!(tree1.find("abc"), Some((3, &2)));
+        assert_eq!(tree1.find("abd"), Some((3, &5)));
+    }
+
     #[test]
     fn should_print_the_tree() {
         let mut tree1 = Tree::new();
@@ -355,7 +418,7 @@ mod tests {
         tree2.insert("abc", 7);
         tree2.insert("xyz", 10);
 
-        tree1.merge("a", tree2);
+        tree1.merge_with("a", tree2, &mut |_, _, b| b);
 
         assert_eq!(
             format!("{:?}", tree1),
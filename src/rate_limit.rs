@@ -0,0 +1,315 @@
+//! Request rate limiting.
+//!
+//! Use `RateLimit` as a router middleware (`Config::middleware`)
+//! or a route middleware (`Route::middleware`).
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures::{future, Future};
+use hyper::{self, header};
+
+use error::Error;
+use middleware::{Middleware, MiddlewareResult};
+use request::{Extensions, Request};
+use timeout::secs;
+
+/// Number of requests allowed within given period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// Maximal number of requests (bucket capacity).
+    pub limit: u32,
+    /// Time it takes to refill the whole bucket.
+    pub period: Duration,
+}
+
+impl Quota {
+    fn rate(&self) -> f64 {
+        f64::from(self.limit) / secs(self.period)
+    }
+}
+
+/// Result of acquiring a token from the store.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    /// Is the request allowed.
+    pub allowed: bool,
+    /// Maximal number of requests (bucket capacity).
+    pub limit: u32,
+    /// Number of requests remaining.
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+    /// Time after which the request can be retried (if not allowed).
+    pub retry_after: Option<Duration>,
+}
+
+/// Result of acquiring a token from the store.
+pub type StoreResult = Box<Future<Item = Decision, Error = Error>>;
+
+/// State of rate limiter buckets.
+pub trait Store: Send + Sync + 'static {
+    /// Takes a single token from the bucket identified by `key`.
+    fn acquire(&self, key: &str, quota: Quota) -> StoreResult;
+}
+
+/// Number of buckets after which full buckets are removed from the memory store.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Token buckets by key.
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<String, (f64, Instant)>,
+    // The store is pruned again only after the number of buckets doubles.
+    prune_at: usize,
+}
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Buckets {
+            buckets: Default::default(),
+            prune_at: PRUNE_THRESHOLD,
+        }
+    }
+}
+
+/// In-memory token bucket store.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl MemoryStore {
+    fn acquire_at(&self, key: &str, quota: Quota, now: Instant) -> Decision {
+        let rate = quota.rate();
+        let capacity = f64::from(quota.limit);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let Buckets { ref mut buckets, ref mut prune_at } = *buckets;
+
+        if buckets.len() >= *prune_at {
+            buckets.retain(|_, &mut (tokens, last)| tokens + secs(now - last) * rate < capacity);
+            *prune_at = cmp::max(PRUNE_THRESHOLD, buckets.len() * 2);
+        }
+
+        let bucket = buckets.entry(key.to_owned()).or_insert((capacity, now));
+        let elapsed = if now > bucket.1 { secs(now - bucket.1) } else { 0.0 };
+        let tokens = (bucket.0 + elapsed * rate).min(capacity);
+        let allowed = tokens >= 1.0;
+        let tokens = if allowed { tokens - 1.0 } else { tokens };
+        *bucket = (tokens, now);
+
+        Decision {
+            allowed,
+            limit: quota.limit,
+            remaining: tokens as u32,
+            reset: duration((capacity - tokens) / rate),
+            retry_after: if allowed { None } else { Some(duration((1.0 - tokens) / rate)) },
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn acquire(&self, key: &str, quota: Quota) -> StoreResult {
+        Box::new(future::ok(self.acquire_at(key, quota, Instant::now())))
+    }
+}
+
+/// Key of requests without peer address.
+const UNKNOWN_PEER: &str = "unknown";
+
+type KeyFn = Fn(&Request) -> Option<String> + Send + Sync;
+
+/// Token bucket rate limiting middleware.
+///
+/// Requests exceeding the limit are rejected with `429 Too Many Requests`.
+/// All responses get `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers.
+pub struct RateLimit {
+    quota: Quota,
+    key: Box<KeyFn>,
+    store: Arc<Store>,
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "RateLimit({:?})", self.quota)
+    }
+}
+
+impl RateLimit {
+    /// Allows `limit` requests per `period` for every client IP.
+    /// Requests with unknown peer address (i.e. over Unix sockets) share a single bucket.
+    /// State is kept in memory.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        RateLimit {
+            quota: Quota { limit, period },
+            key: Box::new(|request| Some(request.remote_addr().map_or_else(
+                || UNKNOWN_PEER.to_owned(),
+                |addr| addr.ip().to_string(),
+            ))),
+            store: Arc::new(MemoryStore::default()),
+        }
+    }
+
+    /// Limit requests by the value of given header (i.e. `Authorization`).
+    /// Requests without the header are not limited.
+    pub fn by_header(self, name: &str) -> Self {
+        let name = name.to_owned();
+        self.by_key(move |request| request.headers()
+            .get_raw(&name)
+            .and_then(|raw| raw.one())
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+        )
+    }
+
+    /// Limit requests by a custom key.
+    /// Requests for which the key is `None` are not limited.
+    pub fn by_key<F>(mut self, key: F) -> Self where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Use custom store (i.e. shared between multiple instances).
+    pub fn store<S: Store>(mut self, store: S) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+}
+
+impl Middleware for RateLimit {
+    fn on_request(&self, mut request: Request) -> MiddlewareResult {
+        let key = match (self.key)(&request) {
+            Some(key) => key,
+            None => return Box::new(future::ok(request)),
+        };
+
+        Box::new(self.store.acquire(&key, self.quota).and_then(move |decision| {
+            if decision.allowed {
+                request.extensions_mut().insert(decision);
+                return Ok(request);
+            }
+
+            let retry_after = decision.retry_after.unwrap_or(decision.reset);
+            let mut error = Error::too_many_requests(format!("Retry after {}s.", ceil_secs(retry_after)))
+                .with_header(header::RetryAfter::Delay(Duration::from_secs(ceil_secs(retry_after))));
+            set_headers(error.headers_mut(), &decision);
+            Err(error)
+        }))
+    }
+
+    fn on_response(&self, mut response: hyper::Response, extensions: &Extensions) -> hyper::Response {
+        if let Some(decision) = extensions.get::<Decision>() {
+            set_headers(response.headers_mut(), decision);
+        }
+        response
+    }
+}
+
+fn set_headers(headers: &mut hyper::Headers, decision: &Decision) {
+    headers.set_raw("RateLimit-Limit", decision.limit.to_string());
+    headers.set_raw("RateLimit-Remaining", decision.remaining.to_string());
+    headers.set_raw("RateLimit-Reset", ceil_secs(decision.reset).to_string());
+}
+
+fn duration(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1_000_000_000.0) as u32)
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use futures::Future;
+    use hyper;
+
+    use error::Error;
+    use middleware::Middleware;
+    use request::Request;
+    use router::Router;
+    use testing::TestClient;
+    use super::{MemoryStore, Quota, RateLimit, PRUNE_THRESHOLD};
+
+    #[test]
+    fn should_refill_tokens() {
+        let store = MemoryStore::default();
+        let quota = Quota { limit: 2, period: Duration::from_secs(10) };
+        let now = Instant::now();
+
+        let first = store.acquire_at("a", quota, now);
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert_eq!(first.reset, Duration::from_secs(5));
+        assert!(store.acquire_at("a", quota, now).allowed);
+        assert!(store.acquire_at("b", quota, now).allowed);
+
+        let rejected = store.acquire_at("a", quota, now);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Some(Duration::from_secs(5)));
+
+        assert!(store.acquire_at("a", quota, now + Duration::from_secs(5)).allowed);
+    }
+
+    #[test]
+    fn should_prune_full_buckets_when_store_doubles() {
+        let store = MemoryStore::default();
+        let quota = Quota { limit: 2, period: Duration::from_secs(10) };
+        let now = Instant::now();
+
+        for i in 0..PRUNE_THRESHOLD {
+            store.acquire_at(&i.to_string(), quota, now);
+        }
+        // None of the buckets is full yet.
+        store.acquire_at("a", quota, now);
+        assert_eq!(store.buckets.lock().unwrap().buckets.len(), PRUNE_THRESHOLD + 1);
+        assert_eq!(store.buckets.lock().unwrap().prune_at, 2 * PRUNE_THRESHOLD);
+
+        // Buckets not used within the period are full again, `a` is not.
+        let later = now + Duration::from_secs(10);
+        store.acquire_at("a", quota, later);
+        for i in PRUNE_THRESHOLD..2 * PRUNE_THRESHOLD - 1 {
+            store.acquire_at(&i.to_string(), quota, later);
+        }
+        assert_eq!(store.buckets.lock().unwrap().buckets.len(), 2 * PRUNE_THRESHOLD);
+
+        store.acquire_at("b", quota, later);
+        let buckets = store.buckets.lock().unwrap();
+        assert_eq!(buckets.buckets.len(), PRUNE_THRESHOLD + 1);
+        assert!(buckets.buckets.contains_key("a"));
+        assert!(!buckets.buckets.contains_key("0"));
+    }
+
+    #[test]
+    fn should_limit_requests_without_peer_address() {
+        let limit = RateLimit::new(1, Duration::from_secs(10));
+        let request = || Request::new(hyper::Request::new(hyper::Method::Get, "/".parse().unwrap()), ());
+
+        assert!(limit.on_request(request()).wait().is_ok());
+        let error = limit.on_request(request()).wait().unwrap_err();
+        assert_eq!(error.code, hyper::StatusCode::TooManyRequests);
+    }
+
+    #[test]
+    fn should_limit_request_rate_per_route() {
+        let mut router = Router::new();
+        router.get("/limited", |_: Request<_>| Ok("ok") as Result<_, Error>)
+            .middleware(RateLimit::new(1, Duration::from_secs(60)).by_header("X-Client"));
+        router.get("/free", |_: Request<_>| Ok("ok") as Result<_, Error>);
+        let client = TestClient::new(router);
+
+        let response = client.get("/limited").header("X-Client", "alice").send();
+        assert_eq!(response.status(), hyper::StatusCode::Ok);
+        assert_eq!(response.header("RateLimit-Remaining"), Some("0"));
+        let response = client.get("/limited").header("X-Client", "alice").send();
+        assert_eq!(response.status(), hyper::StatusCode::TooManyRequests);
+        assert_eq!(response.header("Retry-After"), Some("60"));
+        assert_eq!(response.header("RateLimit-Limit"), Some("1"));
+        assert_eq!(client.get("/free").header("X-Client", "alice").send().status(), hyper::StatusCode::Ok);
+    }
+}
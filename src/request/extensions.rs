@@ -0,0 +1,72 @@
+//! Request-scoped extensions.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A typed map of request-scoped values.
+///
+/// Allows middlewares to pass data (like the authenticated user or request id)
+/// to handlers and response post-processing.
+/// Values are immutable once inserted, use `insert` to replace them.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<Any + Send + Sync>>,
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Extensions({})", self.map.len())
+    }
+}
+
+impl Extensions {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Extensions::default()
+    }
+
+    /// Inserts a value, replacing previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Returns a reference to the value of given type.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns `true` if the value of given type is present.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Removes the value of given type.
+    /// Returns `true` if the value was present.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Extensions;
+
+    #[derive(Debug, PartialEq)]
+    struct User(&'static str);
+
+    #[test]
+    fn should_store_values_by_type() {
+        let mut extensions = Extensions::new();
+        extensions.insert(User("alice"));
+        extensions.insert(5u64);
+        extensions.insert(User("bob"));
+
+        assert_eq!(extensions.get::<User>(), Some(&User("bob")));
+        assert_eq!(extensions.get::<u64>(), Some(&5));
+        assert!(!extensions.contains::<u32>());
+        assert!(extensions.remove::<u64>());
+        assert_eq!(extensions.get::<u64>(), None);
+    }
+}
@@ -1,15 +1,23 @@
 //! Resty request wrapper.
 
+use std::sync::Arc;
+use std::mem;
 use hyper;
 use futures::{self, Stream, Future};
 use serde;
 use serde_json;
 
 use error;
+use state::States;
+use tls::PeerCertificate;
+use urls::{UrlParams, Urls};
+use validation::{self, Validate};
 
+mod extensions;
 pub mod params;
 pub mod url_parser;
 
+pub use self::extensions::Extensions;
 pub use self::params::Params;
 
 /// Request parsing error.
@@ -19,28 +27,132 @@ pub enum Error {
     Serde(serde_json::Error),
     /// Hyper error while reading the body.
     Hyper(hyper::Error),
+    /// Deserialized payload is invalid.
+    Validation(validation::Errors),
 }
 
 impl From<Error> for error::Error {
     fn from(err: Error) -> Self {
-        error::Error::bad_request(
-            "Unable to parse request as JSON.",
-            format!("{:?}", err),
-        )
+        match err {
+            Error::Validation(errors) => errors.into(),
+            err => error::Error::bad_request(
+                "Unable to parse request as JSON.",
+                format!("{:?}", err),
+            ),
+        }
     }
 }
 
+/// Id of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
 /// Resty Request wrapper.
 #[derive(Debug)]
 pub struct Request<P = ()> {
     request: hyper::Request,
     params: Option<P>,
+    states: States,
+    extensions: Extensions,
 }
 
 impl<P> Request<P> {
     /// Creates new instance of request
     pub fn new(request: hyper::Request, params: P) -> Self {
-        Request { request, params: Some(params) }
+        Request {
+            request,
+            params: Some(params),
+            states: Default::default(),
+            extensions: Default::default(),
+        }
+    }
+
+    /// Replaces params of this request.
+    pub(crate) fn with_params<T>(self, params: T) -> Request<T> {
+        Request {
+            request: self.request,
+            params: Some(params),
+            states: self.states,
+            extensions: self.extensions,
+        }
+    }
+
+    /// Moves the request out, leaving an empty one in its place.
+    pub(crate) fn take(&mut self) -> Self {
+        let empty = Request {
+            request: hyper::Request::new(hyper::Method::Get, Default::default()),
+            params: None,
+            states: self.states.clone(),
+            extensions: Default::default(),
+        };
+        mem::replace(self, empty)
+    }
+
+    /// Sets the shared states available to this request.
+    pub(crate) fn set_states(&mut self, states: States) {
+        self.states = states;
+    }
+
+    /// Returns the shared state of given type.
+    ///
+    /// If multiple routers in the composition chain provide the state of the same type
+    /// the one closest to the handler is returned.
+    pub fn state<S: Send + Sync + 'static>(&self) -> Result<Arc<S>, error::Error> {
+        self.states.get().ok_or_else(|| error::Error::internal(
+            "Requested state is not available. That's most likely a bug in the handler.",
+            ::std::any::type_name::<S>(),
+        ))
+    }
+
+    /// Builds the path of the route with given name (see `Route::name`).
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, error::Error> {
+        Ok(self.state::<Urls>()?.url_for(name, params)?)
+    }
+
+    /// Builds the path of the route with given name with params generated by `url!` (see `UrlParams`).
+    pub fn url_for_typed<T: UrlParams>(&self, name: &str, params: &T) -> Result<String, error::Error> {
+        Ok(self.state::<Urls>()?.url_for_typed(name, params)?)
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &hyper::Method {
+        self.request.method()
+    }
+
+    /// Returns the request URI.
+    pub fn uri(&self) -> &hyper::Uri {
+        self.request.uri()
+    }
+
+    /// Returns the request headers.
+    pub fn headers(&self) -> &hyper::Headers {
+        self.request.headers()
+    }
+
+    /// Returns the address of the connected peer (if known).
+    #[allow(deprecated)]
+    pub fn remote_addr(&self) -> Option<::std::net::SocketAddr> {
+        self.request.remote_addr()
+    }
+
+    /// Returns the verified certificate of the peer (for TLS connections with client authentication).
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.extensions.get()
+    }
+
+    /// Returns request-scoped extensions.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns mutable request-scoped extensions.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Returns the id of this request (if enabled with `Config::request_id`).
+    pub fn request_id(&self) -> Option<&str> {
+        self.extensions.get::<RequestId>().map(|id| id.0.as_str())
     }
 
     /// Returns params reference.
@@ -53,6 +165,11 @@ impl<P> Request<P> {
         self.params.take().unwrap()
     }
 
+    /// Takes the body out of the request, leaving an empty one.
+    pub(crate) fn take_body(&mut self) -> hyper::Body {
+        self.request.body_mut().take().unwrap_or_default()
+    }
+
     /// Read the body of this request and deserialize it from JSON.
     /// Returns error in case the request body cannot be read or deserialization fails.
     pub fn json<T>(self) -> JsonResult<T> where
@@ -60,9 +177,18 @@ impl<P> Request<P> {
     {
         self.request.body().concat2().then(deserialize)
     }
+
+    /// Read the body of this request, deserialize it from JSON and validate.
+    /// Returns error in case the request body cannot be read, deserialization fails
+    /// or the deserialized value is invalid.
+    pub fn valid_json<T>(self) -> JsonResult<T> where
+        T: for<'a> serde::de::Deserialize<'a> + Validate,
+    {
+        self.request.body().concat2().then(deserialize_valid)
+    }
 }
 
-fn deserialize<T: for<'a> serde::de::Deserialize<'a>>(chunk: Result<hyper::Chunk, hyper::Error>) -> Result<T, Error> {
+pub(crate) fn deserialize<T: for<'a> serde::de::Deserialize<'a>>(chunk: Result<hyper::Chunk, hyper::Error>) -> Result<T, Error> {
     match chunk {
         Ok(chunk) => match serde_json::from_slice(&*chunk) {
             Ok(res) => Ok(res),
@@ -72,6 +198,14 @@ fn deserialize<T: for<'a> serde::de::Deserialize<'a>>(chunk: Result<hyper::Chunk
     }
 }
 
+fn deserialize_valid<T>(chunk: Result<hyper::Chunk, hyper::Error>) -> Result<T, Error> where
+    T: for<'a> serde::de::Deserialize<'a> + Validate,
+{
+    let value: T = deserialize(chunk)?;
+    value.validate().map_err(Error::Validation)?;
+    Ok(value)
+}
+
 type JsonResult<T> = futures::Then<
     futures::stream::Concat2<hyper::Body>,
     Result<T, Error>,
@@ -85,30 +85,51 @@ pub trait Parser: Send + Sync + 'static {
     /// Returns number of expected params and param names.
     fn expected_params(&self) -> (usize, String);
 
+    /// Returns names and types (as declared in Rust) of the params.
+    ///
+    /// Used to document the routes (see `Router::openapi`).
+    fn param_types(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
     /// Parser URL and return params
     fn parse(&self, uri: &hyper::Uri, skip: usize) -> Result<Self::Params, Error>;
 }
 
+/// Returns true if the pattern (i.e. `/static/{*path}`) ends with a catch-all parameter,
+/// matching the rest of the path (including slashes).
+pub fn is_catch_all(pattern: &str) -> bool {
+    pattern.rsplit('/').take(1).any(|last| last.starts_with("{*") && last.ends_with('}'))
+}
+
 /// A standard parser which processes params dynamically.
+///
+/// The last parameter can be a catch-all one (`{*path}`), matching the rest of the path.
 #[derive(Debug, Default)]
 pub struct StdParser {
     params: Vec<(usize, String)>,
     segments: Vec<(usize, String)>,
     expected: usize,
+    catch_all: bool,
 }
 
 impl StdParser {
     /// Create new standard params and parse given string for params patterns.
     pub fn params(params: &str) -> Self {
-        let mut it = params.split('/');
+        let mut it = params.split('/').peekable();
         let mut params = vec![];
         let mut segments = vec![];
         let mut pos = 0;
+        let mut catch_all = false;
 
         while let Some(param) = it.next() {
             let len = param.len();
             if len > 0 && &param[0..1] == "{" && &param[len - 1..] == "}" {
-                let name = &param[1 .. len-1];
+                let mut name = &param[1 .. len-1];
+                if name.starts_with('*') && it.peek().is_none() {
+                    name = &name[1..];
+                    catch_all = true;
+                }
                 params.push((pos, name.to_owned()));
             } else {
                 segments.push((pos, param.to_owned()));
@@ -120,6 +141,7 @@ impl StdParser {
             params,
             segments,
             expected: pos,
+            catch_all,
         }
     }
 }
@@ -127,7 +149,18 @@ impl Parser for StdParser {
     type Params = DynamicParams;
 
     fn expected_params(&self) -> (usize, String) {
-        (self.expected, self.params.iter().fold(String::new(), |acc, param| acc + "/{" + &param.1 + "}"))
+        let catch_all = if self.catch_all { "*" } else { "" };
+        let last = self.expected.saturating_sub(1);
+        let mut segments = self.params.iter()
+            .map(|&(pos, ref name)| (pos, format!("/{{{}{}}}", if pos == last { catch_all } else { "" }, name)))
+            .chain(self.segments.iter().map(|&(pos, ref segment)| (pos, format!("/{}", segment))))
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.0);
+        (self.expected, segments.into_iter().fold(String::new(), |acc, segment| acc + &segment.1))
+    }
+
+    fn param_types(&self) -> Vec<(String, String)> {
+        self.params.iter().map(|(_, name)| (name.clone(), "String".into())).collect()
     }
 
     fn parse(&self, uri: &hyper::Uri, skip: usize) -> Result<Self::Params, Error> {
@@ -135,19 +168,25 @@ impl Parser for StdParser {
         if self.expected == 0 && !path.is_empty() {
             Err(Error::NotFound)
         } else {
-            DynamicParams::validate(
+            let mut params = DynamicParams::validate(
                 self.params.clone(),
                 self.segments.clone(),
                 path.into(),
-            )
+            )?;
+            if self.catch_all {
+                params.catch_all = self.params.last().map(|param| param.0);
+            }
+            Ok(params)
         }
     }
 }
 
 /// Dynamic parameters.
+#[derive(Debug, Clone)]
 pub struct DynamicParams {
     params: Vec<(usize, String)>,
     path: String,
+    catch_all: Option<usize>,
 }
 
 impl DynamicParams {
@@ -179,6 +218,7 @@ impl DynamicParams {
         Ok(DynamicParams {
             params,
             path,
+            catch_all: None,
         })
     }
 
@@ -192,9 +232,19 @@ impl DynamicParams {
         Err(Error::UnknownParameter(name.into()))
     }
 
+    /// Returns the names of all declared parameters.
+    pub fn names(&self) -> Vec<&str> {
+        self.params.iter().map(|(_, name)| name.as_str()).collect()
+    }
+
     /// Retrieve a string value of a parameter by given name.
     pub fn get_str(&self, name: &str) -> Result<&str, Error> {
         let pos = self.find(name)?;
+        if self.catch_all == Some(pos) {
+            // The rest of the path (possibly empty).
+            let start = self.path.split('/').take(pos).map(|segment| segment.len() + 1).sum::<usize>();
+            return Ok(self.path.get(start..).unwrap_or(""));
+        }
         self.path.split('/').nth(pos).ok_or_else(|| Error::NotFound)
     }
 
@@ -247,5 +297,20 @@ mod tests {
         assert_eq!(parsed.get::<f64>("id").unwrap(), 5.0f64);
         assert_eq!(parsed.get::<u32>("id").unwrap(), 5u32);
         assert_eq!(parsed.get::<u64>("id").unwrap(), 5u64);
+        assert_eq!(params.parser.expected_params(), (2, "/{id}/xxx".into()));
+    }
+
+    #[test]
+    fn should_parse_catch_all_param() {
+        let params: Params = "/static/{dir}/{*path}".into();
+        assert_eq!(params.prefix, "/static/");
+        assert_eq!(params.parser.expected_params(), (2, "/{dir}/{*path}".into()));
+        let uri = "http://localhost/static/css/vendor/a.css".parse().unwrap();
+        let parsed = params.parser.parse(&uri, params.prefix.len()).unwrap();
+        assert_eq!(parsed.get_str("dir").unwrap(), "css");
+        assert_eq!(parsed.get_str("path").unwrap(), "vendor/a.css");
+        let uri = "http://localhost/static/css".parse().unwrap();
+        let parsed = params.parser.parse(&uri, params.prefix.len()).unwrap();
+        assert_eq!(parsed.get_str("path").unwrap(), "");
     }
 }
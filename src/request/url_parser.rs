@@ -24,6 +24,23 @@ macro_rules! url_internal {
             $($param: $type,)*
         }
 
+        #[allow(dead_code)]
+        impl MyParams {
+            $(
+                /// Sets the value of the parameter (see `UrlParams`).
+                pub fn $param(mut self, $param: $type) -> Self {
+                    self.$param = $param;
+                    self
+                }
+            )*
+        }
+
+        impl $crate::UrlParams for MyParams {
+            fn url_params(&self) -> Vec<(&'static str, String)> {
+                vec![$((stringify!($param), self.$param.to_string()),)*]
+            }
+        }
+
         impl $crate::request::params::Parser for MyParams {
             type Params = MyParams;
 
@@ -34,6 +51,10 @@ macro_rules! url_internal {
                 (count, s)
             }
 
+            fn param_types(&self) -> Vec<(String, String)> {
+                vec![$((stringify!($param).into(), stringify!($type).into()),)*]
+            }
+
             fn parse(&self, uri: &$crate::Uri, skip: usize) -> Result<Self::Params, $crate::request::params::Error> {
                 let mut it = uri.path()[skip..].split('/');
                 parser!(it, $($data)*);
@@ -144,4 +165,8 @@ fn url_parser() {
     let parsed = url.parser.parse(&uri, url.prefix.len()).unwrap();
     assert_eq!(parsed.id, 5);
     assert_eq!(parsed.a, "3".to_owned());
+    assert_eq!(url.parser.param_types(), vec![
+        ("id".to_owned(), "usize".to_owned()),
+        ("a".to_owned(), "String".to_owned()),
+    ]);
 }
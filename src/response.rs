@@ -10,6 +10,15 @@ pub struct Response {
     pub(crate) response: hyper::Response,
 }
 
+impl Response {
+    /// Creates a response from raw `hyper::Response`.
+    ///
+    /// Useful for responses that are not JSON.
+    pub fn raw(response: hyper::Response) -> Self {
+        Response { response }
+    }
+}
+
 impl Into<hyper::Response> for Response {
     fn into(self) -> hyper::Response {
         self.response
@@ -24,6 +33,7 @@ impl<T: serde::Serialize> From<T> for Response {
                 let response = hyper::Response::new()
                     .with_status(hyper::StatusCode::Ok)
                     .with_header(header::ContentType::json())
+                    .with_header(header::ContentLength(serialized.len() as u64))
                     .with_body(serialized);
                 Response { response }
             },
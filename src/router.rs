@@ -1,17 +1,39 @@
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use hyper;
 use futures::{future, Future, IntoFuture};
+use serde::Serialize;
+use serde_json;
 
+use access_log::AccessLog;
+use auth::{Challenge, Permissions};
+use concurrency::{Guard, Limiter};
 use config::{Config, MaterializedConfig};
 use error::Error;
-use request::{params, Params, Request};
+use extract::FromRequest;
+use health::Health;
+use metrics::{self, Metrics};
+use request::{params, Extensions, Params, Request};
 use response::Response;
-use server::{Server, Listening};
+use middleware::{Middleware, MiddlewareResult, Middlewares};
+use openapi::{self, Schema};
+use server::{Server, ServerConfig, Listening};
+use state::States;
+use static_files::StaticFiles;
+use timeout::timeout;
+use urls::{UrlError, UrlParams, Urls};
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Handle;
+use tls::TlsConfig;
 use prefix_tree;
 
 
 pub type HandlerResult = Box<Future<Item = hyper::Response, Error = hyper::Error>>;
-pub type BoxHandler = Box<Fn(hyper::Request, usize) -> HandlerResult + Sync + Send>;
+pub type EndpointResult = Box<Future<Item = (hyper::Response, Vec<Guard>), Error = hyper::Error>>;
+pub type Handler = Arc<Fn(Request, usize) -> HandlerResult + Sync + Send>;
+type DispatchResult = Box<Future<Item = (hyper::Response, Extensions), Error = hyper::Error>>;
 pub type Routes = prefix_tree::Tree<Endpoint>;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -72,7 +94,21 @@ impl<'a> From<&'a hyper::Method> for Method {
     }
 }
 
-const MAX_NUMBER_OF_ENDPOINTS: usize = 6;
+pub(crate) const MAX_NUMBER_OF_ENDPOINTS: usize = 6;
+
+/// Options overriding the endpoint config for a single route.
+#[derive(Debug, Default, Clone)]
+pub struct RouteConfig {
+    timeout: Option<Duration>,
+    middlewares: Middlewares,
+    permissions: Vec<String>,
+    param_types: Vec<(String, String)>,
+    request_schema: Option<serde_json::Value>,
+    response_schema: Option<serde_json::Value>,
+    name: Option<String>,
+    max_concurrent_requests: Option<usize>,
+    limiter: Option<Limiter>,
+}
 
 pub enum EndpointHandler {
     None,
@@ -80,7 +116,8 @@ pub enum EndpointHandler {
         // TODO [ToDr] Many methods with single handler?
         method: Method,
         params: (usize, String),
-        handler: BoxHandler,
+        handler: Handler,
+        route: Box<RouteConfig>,
     }
 }
 
@@ -99,6 +136,7 @@ pub struct Endpoint {
     base_config: Config,
     config: MaterializedConfig,
     allowed_methods: Vec<hyper::Method>,
+    states: States,
 }
 
 impl fmt::Display for Endpoint {
@@ -111,8 +149,13 @@ impl fmt::Display for Endpoint {
                 EndpointHandler::None => {
                     return Ok(());
                 },
-                EndpointHandler::Some { ref method, ref params, .. } => {
-                    writeln!(fmt, "  {} {}", method, if params.0 == 0 { "/" } else { &params.1 })?;
+                EndpointHandler::Some { ref method, ref params, ref route, .. } => {
+                    write!(fmt, "  {} {}", method, if params.0 == 0 { "/" } else { &params.1 })?;
+                    let permissions = self.required_permissions(route);
+                    if !permissions.is_empty() {
+                        write!(fmt, " [requires: {}]", permissions.join(", "))?;
+                    }
+                    writeln!(fmt)?;
                 }
             }
         }
@@ -121,7 +164,7 @@ impl fmt::Display for Endpoint {
 }
 
 impl Endpoint {
-    pub fn with_config(base_config: Config) -> Self {
+    pub fn with_config(base_config: Config, states: States) -> Self {
         use self::EndpointHandler::None;
         let config = base_config.materialize();
         Endpoint {
@@ -129,6 +172,7 @@ impl Endpoint {
             base_config,
             config,
             allowed_methods: vec![],
+            states,
         }
     }
 
@@ -140,7 +184,7 @@ impl Endpoint {
         self.config = self.base_config.materialize();
     }
 
-    pub fn add(&mut self, method: Method, params: (usize, String), handler: BoxHandler) -> bool {
+    pub fn add(&mut self, method: Method, params: (usize, String), handler: Handler) -> bool {
         for i in 0..MAX_NUMBER_OF_ENDPOINTS {
             match self.handlers[i] {
                 EndpointHandler::Some { .. } => continue,
@@ -150,6 +194,7 @@ impl Endpoint {
                 method,
                 params,
                 handler,
+                route: Default::default(),
             };
             let m = (&method).into();
             if !self.allowed_methods.contains(&m) {
@@ -161,69 +206,238 @@ impl Endpoint {
         false
     }
 
-    pub fn handle(&self, m: Method, req: hyper::Request, prefix: usize) -> HandlerResult {
-        if self.config.extra_headers.is_empty() {
-            Box::new(self.handle_internal(m, req, prefix))
-        } else {
-            let extra_headers = self.config.extra_headers.clone();
-            Box::new(self.handle_internal(m, req, prefix).map(move |mut response| {
-                {
-                    let mut headers = response.headers_mut();
-                    for (name, val) in extra_headers {
-                        // Don't override headers that were provided with the response.
-                        if headers.get_raw(&name).is_none() {
-                            headers.set_raw(name, val);
-                        }
-                    }
-                }
-                response
-            }))
+    /// Returns params of the route that would handle all requests of a new route
+    /// with given method and params.
+    fn shadowed_by(&self, method: Method, params: &(usize, String)) -> Option<&(usize, String)> {
+        let catch_all = params::is_catch_all(&params.1);
+        self.routes()
+            .find(|&(m, p, _)| m == method && match (params::is_catch_all(&p.1), catch_all) {
+                (false, false) => p.0 == params.0,
+                // Catch-all route handles all paths with at least `p.0 - 1` segments (see `find`).
+                (true, false) => params.0 + 1 >= p.0,
+                (true, true) => params.0 >= p.0,
+                (false, true) => false,
+            })
+            .map(|(_, p, _)| p)
+    }
+
+    /// Returns the config of the most recently added route.
+    fn last_route_mut(&mut self) -> Option<&mut RouteConfig> {
+        self.handlers.iter_mut().rev().filter_map(|handler| match *handler {
+            EndpointHandler::None => None,
+            EndpointHandler::Some { ref mut route, .. } => Some(&mut **route),
+        }).next()
+    }
+
+    /// Returns method, params and config of every route handled by the endpoint.
+    fn routes(&self) -> impl Iterator<Item = (Method, &(usize, String), &RouteConfig)> {
+        self.handlers.iter().filter_map(|handler| match *handler {
+            EndpointHandler::None => None,
+            EndpointHandler::Some { method, ref params, ref route, .. } => Some((method, params, &**route)),
+        })
+    }
+
+    /// Returns permissions required by the endpoint config and given route.
+    fn required_permissions(&self, route: &RouteConfig) -> Vec<String> {
+        let mut permissions = self.config.permissions.clone();
+        for permission in &route.permissions {
+            if !permissions.contains(permission) {
+                permissions.push(permission.clone());
+            }
         }
+        permissions
     }
 
-    fn handle_internal(&self, m: Method, req: hyper::Request, prefix: usize) -> future::Either<
-        HandlerResult,
-        future::FutureResult<hyper::Response, hyper::Error>,
-    > {
-        use self::future::Either;
+    /// Adds states with lower priority than the ones already set.
+    fn add_states(&mut self, states: &States) {
+        self.states.extend(states);
+    }
+
+    /// Handles the request.
+    ///
+    /// Returns the response together with slots of concurrency limits
+    /// that should be held until the response body is sent.
+    pub fn handle(&self, m: Method, mut req: Request, prefix: usize) -> EndpointResult {
+        req.set_states(self.states.clone());
 
-        let expected = {
-            let path = &req.path()[prefix..];
-            if path.is_empty() {
-                0
-            } else {
-                path.split('/').count()
+        let mut guards = vec![];
+        let metrics = self.config.metrics.clone().map(|metrics| {
+            guards.push(Guard::new(&metrics.in_flight_counter()));
+            // Label with route template to keep the cardinality bounded.
+            let path = req.uri().path();
+            let route = self.route_pattern(m, path, prefix).unwrap_or_else(|| metrics::UNMATCHED_ROUTE.to_owned());
+            (metrics, route, Instant::now())
+        });
+        let middlewares = self.config.middlewares.clone();
+        let extra_headers = self.config.extra_headers.clone();
+        let extensions = req.extensions().clone();
+        let request_id = req.request_id().map(ToOwned::to_owned);
+        let limiter = self.route_config(m, &req.uri().path()[prefix..]).and_then(|route| route.limiter.as_ref());
+        let response: DispatchResult = match limiter.map(Limiter::process) {
+            Some(Err(error)) => Box::new(future::ok((error.with_request_id(request_id).into(), extensions))),
+            Some(Ok(guard)) => {
+                guards.push(guard);
+                Box::new(self.handle_internal(m, req, prefix))
+            },
+            None => Box::new(self.handle_internal(m, req, prefix)),
+        };
+        Box::new(response.map(move |(mut response, extensions)| {
+            for middleware in middlewares.0.iter().rev() {
+                response = middleware.on_response(response, &extensions);
+            }
+
+            {
+                let mut headers = response.headers_mut();
+                for (name, val) in extra_headers {
+                    // Don't override headers that were provided with the response.
+                    if headers.get_raw(&name).is_none() {
+                        headers.set_raw(name, val);
+                    }
+                }
             }
+
+            if let Some((metrics, route, started)) = metrics {
+                metrics.observe(&m.to_string(), &route, response.status().as_u16(), started.elapsed());
+            }
+            (response, guards)
+        }))
+    }
+
+    /// Finds a handler for given method and path (without prefix).
+    /// Returns `Err(true)` if there are handlers for given method, but none of them matches the path.
+    fn find(&self, m: Method, path: &str) -> Result<(&(usize, String), &Handler, &RouteConfig), bool> {
+        let expected = if path.is_empty() {
+            0
+        } else {
+            path.split('/').count()
         };
         let mut method_found = false;
 
         for i in 0..MAX_NUMBER_OF_ENDPOINTS {
             match self.handlers[i] {
                 EndpointHandler::None => break,
-                EndpointHandler::Some { ref method, ref params, ref handler } => {
+                EndpointHandler::Some { ref method, ref params, ref handler, ref route } => {
                     if method != &m {
                         continue;
                     }
 
                     method_found = true;
 
-                    if params.0 != expected {
+                    // Catch-all parameter matches the rest of the path (possibly empty).
+                    let matches = if params::is_catch_all(&params.1) {
+                        expected + 1 >= params.0
+                    } else {
+                        params.0 == expected
+                    };
+                    if !matches {
                         continue;
                     }
 
-                    return Either::A(handler(req, prefix));
+                    return Ok((params, handler, route));
                 },
             }
         }
 
+        Err(method_found)
+    }
+
+    /// Returns the route pattern (i.e. `/v1/products/{id}`) of the handler
+    /// that would handle request with given method and path.
+    pub fn route_pattern(&self, m: Method, path: &str, prefix: usize) -> Option<String> {
+        let params = match self.find(m, &path[prefix..]) {
+            Ok((params, _, _)) => params,
+            Err(false) if m == Method::Head && self.config.handle_head => {
+                return self.route_pattern(Method::Get, path, prefix);
+            },
+            Err(_) => return None,
+        };
+
+        Some(route_pattern(&path[..prefix], params))
+    }
+
+    /// Returns the config of the route that would handle request with given method and path (without prefix).
+    fn route_config(&self, m: Method, path: &str) -> Option<&RouteConfig> {
+        match self.find(m, path) {
+            Ok((_, _, route)) => Some(route),
+            Err(false) if m == Method::Head && self.config.handle_head => self.route_config(Method::Get, path),
+            Err(_) => None,
+        }
+    }
+
+    /// Runs the middlewares and invokes the handler.
+    fn invoke(&self, handler: &Handler, route: &RouteConfig, req: Request, prefix: usize) -> DispatchResult {
+        let handler = handler.clone();
+        let permissions = self.required_permissions(route);
+        if self.config.auth.is_none() && self.config.middlewares.0.is_empty() && route.middlewares.0.is_empty() && permissions.is_empty() {
+            let extensions = req.extensions().clone();
+            return Box::new(handler(req, prefix).map(move |response| (response, extensions)));
+        }
+
+        let initial_extensions = req.extensions().clone();
+        let request_id = req.request_id().map(ToOwned::to_owned);
+        let mut request: MiddlewareResult = Box::new(future::ok(req));
+        for middleware in self.config.auth.iter().flat_map(|auth| &auth.0) {
+            let middleware = middleware.clone();
+            request = Box::new(request.and_then(move |req| middleware.on_request(req)));
+        }
+        if !permissions.is_empty() {
+            request = Box::new(request.and_then(move |req| authorize(req, &permissions)));
+        }
+        for middleware in self.config.middlewares.0.iter().chain(&route.middlewares.0) {
+            let middleware = middleware.clone();
+            request = Box::new(request.and_then(move |req| middleware.on_request(req)));
+        }
+
+        // Route middlewares process the response before the endpoint ones.
+        let route_middlewares = route.middlewares.clone();
+        Box::new(request.then(move |result| match result {
+            Ok(req) => {
+                let extensions = req.extensions().clone();
+                future::Either::A(handler(req, prefix).map(move |response| (response, extensions)))
+            },
+            Err(err) => future::Either::B(future::ok((err.with_request_id(request_id).into(), initial_extensions))),
+        }).map(move |(mut response, extensions)| {
+            for middleware in route_middlewares.0.iter().rev() {
+                response = middleware.on_response(response, &extensions);
+            }
+            (response, extensions)
+        }))
+    }
+
+    fn handle_internal(&self, m: Method, req: Request, prefix: usize) -> future::Either<
+        DispatchResult,
+        future::FutureResult<(hyper::Response, Extensions), hyper::Error>,
+    > {
+        use self::future::Either;
+
+        let method_found = match self.find(m, &req.uri().path()[prefix..]) {
+            Ok((_, handler, route)) => return Either::A(match route.timeout.or(self.config.timeout) {
+                None => self.invoke(handler, route, req, prefix),
+                Some(duration) => {
+                    let extensions = req.extensions().clone();
+                    let request_id = req.request_id().map(ToOwned::to_owned);
+                    Box::new(timeout(self.invoke(handler, route, req, prefix), duration).map(move |result| {
+                        result.unwrap_or_else(|| {
+                            let details = format!("The request was not processed within {:?}.", duration);
+                            (Error::timeout(details).with_request_id(request_id).into(), extensions)
+                        })
+                    }))
+                },
+            }),
+            Err(method_found) => method_found,
+        };
+
+        let extensions = req.extensions().clone();
+        let request_id = req.request_id().map(ToOwned::to_owned);
         match (m, method_found) {
             (_, true) => {
-                Either::B(future::ok(Error::not_found("Unable to find a handler.").into()))
+                let error = Error::not_found("Unable to find a handler.").with_request_id(request_id);
+                Either::B(future::ok((error.into(), extensions)))
             },
             (Method::Head, false) if self.config.handle_head => {
-                Either::A(Box::new(self.handle_internal(Method::Get, req, prefix).map(|mut response| {
+                Either::A(Box::new(self.handle_internal(Method::Get, req, prefix).map(|(mut response, extensions)| {
                     response.set_body(vec![]);
-                    response
+                    (response, extensions)
                 })))
             },
             (Method::Options, false) if self.config.handle_options => {
@@ -231,7 +445,7 @@ impl Endpoint {
                 let res = hyper::Response::new()
                     .with_status(hyper::StatusCode::Ok)
                     .with_header(hyper::header::Allow(allowed_methods));
-                Either::B(future::ok(res))
+                Either::B(future::ok((res, extensions)))
             },
             _ => {
                 let allowed_methods = self.allowed_methods.clone();
@@ -249,20 +463,243 @@ impl Endpoint {
                 let mut res: hyper::Response = Error::method_not_allowed(
                     format!("Method {} is not allowed.", m),
                     format!("Allowed methods: {}", allowed_str)
-                ).into();
+                ).with_request_id(request_id).into();
                 res.headers_mut().set(hyper::header::Allow(allowed_methods));
-                Either::B(future::ok(res))
+                Either::B(future::ok((res, extensions)))
             }
         }
     }
 }
 
+/// Rejects the request with `403` unless the authenticated principal has all required permissions.
+/// Requests without a principal are rejected with `401` (and the challenge of the authenticator).
+fn authorize(req: Request, required: &[String]) -> Result<Request, Error> {
+    let missing = match req.extensions().get::<Permissions>() {
+        Some(permissions) => permissions.missing(required),
+        None => return Err(match req.extensions().get::<Challenge>() {
+            Some(challenge) => challenge.unauthorized("Missing credentials."),
+            None => Error::unauthorized("Missing credentials."),
+        }),
+    };
+    if missing.is_empty() {
+        Ok(req)
+    } else {
+        Err(Error::forbidden(format!("Missing permissions: {}", missing.join(", "))))
+    }
+}
+
+/// Joins the endpoint prefix and handler params into a route pattern.
+fn route_pattern(prefix: &str, params: &(usize, String)) -> String {
+    if params.0 == 0 {
+        prefix.to_owned()
+    } else {
+        format!("{}{}", prefix.trim_end_matches('/'), params.1)
+    }
+}
+
+/// Conflict between routes detected during registration (see `Router::conflicts`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The route is never invoked, since requests are handled by a route with the same method
+    /// and number of segments (or a catch-all one) registered earlier.
+    Shadowed {
+        /// HTTP method.
+        method: String,
+        /// Pattern of the shadowed route.
+        pattern: String,
+        /// Pattern of the route handling the requests.
+        by: String,
+    },
+    /// The endpoint was replaced while composing routers with `Router::add`
+    /// and all its routes were dropped.
+    Replaced {
+        /// Prefix of the endpoint.
+        prefix: String,
+        /// Dropped routes.
+        routes: Vec<DroppedRoute>,
+    },
+    /// The name is used by routes with different patterns (see `Route::name`),
+    /// URLs are built using the first one.
+    DuplicateName {
+        /// Name of the routes.
+        name: String,
+        /// Patterns of the routes.
+        patterns: Vec<String>,
+    },
+}
+
+/// Route dropped when its endpoint was replaced (see `Conflict::Replaced`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedRoute {
+    /// HTTP method.
+    pub method: String,
+    /// Route pattern (i.e. `/v1/products/{id}`).
+    pub pattern: String,
+}
+
+impl fmt::Display for DroppedRoute {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} {}", self.method, self.pattern)
+    }
+}
+
+impl Conflict {
+    fn with_prefix(self, prefix: &str) -> Self {
+        match self {
+            Conflict::Shadowed { method, pattern, by } => Conflict::Shadowed {
+                method,
+                pattern: format!("{}{}", prefix, pattern),
+                by: format!("{}{}", prefix, by),
+            },
+            Conflict::Replaced { prefix: p, routes } => Conflict::Replaced {
+                prefix: format!("{}{}", prefix, p),
+                routes: routes.into_iter().map(|route| DroppedRoute {
+                    method: route.method,
+                    pattern: format!("{}{}", prefix, route.pattern),
+                }).collect(),
+            },
+            Conflict::DuplicateName { name, patterns } => Conflict::DuplicateName {
+                name,
+                patterns: patterns.into_iter().map(|pattern| format!("{}{}", prefix, pattern)).collect(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Conflict::Shadowed { ref method, ref pattern, ref by } =>
+                write!(fmt, "{} {} is shadowed by {} {}", method, pattern, method, by),
+            Conflict::Replaced { ref prefix, ref routes } => {
+                let routes = routes.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(fmt, "Endpoint {} was replaced, dropped routes: {}", prefix, routes.join(", "))
+            },
+            Conflict::DuplicateName { ref name, ref patterns } =>
+                write!(fmt, "Route name {:?} is used by multiple routes: {}", name, patterns.join(", ")),
+        }
+    }
+}
+
+/// Description of a single route (see `Router::route_table`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteInfo {
+    /// Prefix of the endpoint the route is registered at.
+    pub prefix: String,
+    /// HTTP method.
+    pub method: String,
+    /// Full route pattern (i.e. `/v1/products/{id}`).
+    pub pattern: String,
+    /// Path parameters in order of appearance.
+    pub params: Vec<ParamInfo>,
+    /// Effective configuration of the route.
+    pub config: RouteConfigInfo,
+}
+
+/// Path parameter of a route.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamInfo {
+    /// Parameter name.
+    pub name: String,
+    /// Rust type of the parameter (`String` unless declared with `url!`).
+    #[serde(rename = "type")]
+    pub param_type: String,
+}
+
+/// Effective configuration of a route (including overrides set via `Route`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteConfigInfo {
+    /// HEAD requests are handled automatically.
+    pub handle_head: bool,
+    /// OPTIONS requests are handled automatically.
+    pub handle_options: bool,
+    /// Names of headers added to every response.
+    pub extra_headers: Vec<String>,
+    /// Number of middlewares (router and route ones).
+    pub middlewares: usize,
+    /// Requests get an id.
+    pub request_id: bool,
+    /// Handler timeout in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// Limit of concurrently processed requests of this route (see `Route::max_concurrent_requests`).
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests are authenticated.
+    pub auth: bool,
+    /// Required permissions.
+    pub permissions: Vec<String>,
+    /// Metrics are collected.
+    pub metrics: bool,
+}
+
+/// A route declared on the router.
+///
+/// Allows to override router config for this particular route.
+#[derive(Debug)]
+pub struct Route<'a> {
+    config: &'a mut RouteConfig,
+}
+
+impl<'a> Route<'a> {
+    /// Overrides the timeout (see `Config::timeout`) for this route.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits the number of requests of this route processed concurrently.
+    ///
+    /// Requests over the limit are immediately rejected with `503`.
+    /// Applies in addition to the server-wide limit (see `Config::max_concurrent_requests`).
+    pub fn max_concurrent_requests(self, max: usize) -> Self {
+        self.config.max_concurrent_requests = Some(max);
+        self.config.limiter = Some(Limiter::new(Some(max), Default::default()));
+        self
+    }
+
+    /// Adds a middleware invoked only for this route
+    /// (after the middlewares set via `Config::middleware`).
+    pub fn middleware<M: Middleware>(self, middleware: M) -> Self {
+        self.config.middlewares.0.push(Arc::new(middleware));
+        self
+    }
+
+    /// Requires given permission (role or scope) for this route
+    /// in addition to the ones required via `Config::require`.
+    ///
+    /// Requests lacking the permission are rejected with `403`.
+    pub fn require(self, permission: &str) -> Self {
+        self.config.permissions.push(permission.into());
+        self
+    }
+
+    /// Names the route, so that its URL can be generated (see `Router::url_for`).
+    pub fn name(self, name: &str) -> Self {
+        self.config.name = Some(name.into());
+        self
+    }
+
+    /// Documents the JSON request body of this route (see `Router::openapi`).
+    pub fn request_schema<T: Schema + ?Sized>(self) -> Self {
+        self.config.request_schema = Some(T::schema());
+        self
+    }
+
+    /// Documents the JSON response body of this route (see `Router::openapi`).
+    pub fn response_schema<T: Schema + ?Sized>(self) -> Self {
+        self.config.response_schema = Some(T::schema());
+        self
+    }
+}
+
 /// Resty router.
 /// TODO [ToDr] More docs
 #[derive(Default, Debug)]
 pub struct Router {
     routes: Routes,
     config: Config,
+    states: States,
+    access_log: Option<Arc<AccessLog>>,
+    conflicts: Vec<Conflict>,
 }
 
 impl Router {
@@ -278,6 +715,71 @@ impl Router {
         r
     }
 
+    /// Adds shared state available to all handlers of this router
+    /// (including sub-routers composed with `add`) via `Request::state`.
+    ///
+    /// In case multiple routers provide state of the same type,
+    /// handlers get the one from the router nearest to them.
+    pub fn with_state<S: Send + Sync + 'static>(mut self, state: S) -> Self {
+        let mut states = States::default();
+        states.push(Arc::new(state));
+        self.states.extend(&states);
+        let f = move |endpoint: &mut Endpoint| endpoint.add_states(&states);
+        self.routes.for_each(&f);
+        self
+    }
+
+    /// Writes an access log entry for every request handled by the server.
+    ///
+    /// The log of a nested router is carried over to the router it's added to (see `add`),
+    /// so it logs requests to all the routes of the server.
+    ///
+    /// # Panics
+    ///
+    /// If the router already has an access log (i.e. carried over from a nested router).
+    pub fn with_access_log(mut self, access_log: AccessLog) -> Self {
+        assert!(self.access_log.is_none(), "The router already has an access log.");
+        self.access_log = Some(Arc::new(access_log));
+        self
+    }
+
+    /// Collects request metrics of all endpoints of this router
+    /// and serves them under given prefix in Prometheus text format.
+    pub fn metrics(&mut self, prefix: &str) {
+        let metrics = Arc::new(Metrics::default());
+        self.config = self.config.clone().metrics(metrics.clone());
+        let config = self.config.clone();
+        let f = move |endpoint: &mut Endpoint| endpoint.add_config(&config);
+        self.routes.for_each(&f);
+
+        self.get(prefix, move |_: Request<_>| {
+            let body = metrics.render();
+            let response = hyper::Response::new()
+                .with_header(hyper::header::ContentType("text/plain; version=0.0.4".parse().expect("Valid mime; qed")))
+                .with_header(hyper::header::ContentLength(body.len() as u64))
+                .with_body(body);
+            Ok(Response::raw(response)) as Result<_, Error>
+        });
+    }
+
+    /// Registers liveness (`{prefix}/live`) and readiness (`{prefix}/ready`) endpoints.
+    ///
+    /// Returns a handle to register readiness checks with.
+    /// Readiness endpoint responds with `503` if any of the required checks fails.
+    pub fn health(&mut self, prefix: &str) -> Health {
+        let health = Health::default();
+        let prefix = prefix.trim_end_matches('/');
+
+        let h = health.clone();
+        self.get(&*format!("{}/live", prefix), move |_: Request<_>| Ok(Response::raw(h.live())) as Result<_, Error>);
+        let h = health.clone();
+        self.get(&*format!("{}/ready", prefix), move |_: Request<_>| h.ready().then(|response| match response {
+            Ok(response) => Ok(Response::raw(response)),
+            Err(()) => Err(Error::internal("Unable to check readiness.", "")),
+        }));
+        health
+    }
+
     /// Pretty-prints the endpoints handled by given router.
     pub fn routes(&self) -> String {
         let mut s = String::new();
@@ -289,24 +791,254 @@ impl Router {
         s
     }
 
+    /// Returns description of every route handled by given router.
+    ///
+    /// The table can be serialized (i.e. to JSON) to diff route sets or generate docs.
+    pub fn route_table(&self) -> Vec<RouteInfo> {
+        let mut table = vec![];
+        for (prefix, endpoint) in self.routes.iter() {
+            let prefix = ::std::str::from_utf8(&prefix).expect("Storing only strings in tree; qed");
+            for (method, params, route) in endpoint.routes() {
+                let pattern = route_pattern(prefix, params);
+                let params = openapi::pattern_param_types(&pattern, &route.param_types).into_iter()
+                    .map(|(name, param_type)| ParamInfo { name, param_type: param_type.to_owned() })
+                    .collect();
+                let config = &endpoint.config;
+                table.push(RouteInfo {
+                    prefix: prefix.to_owned(),
+                    method: method.to_string(),
+                    pattern,
+                    params,
+                    config: RouteConfigInfo {
+                        handle_head: config.handle_head,
+                        handle_options: config.handle_options,
+                        extra_headers: config.extra_headers.iter().map(|h| h.0.clone()).collect(),
+                        middlewares: config.middlewares.0.len() + route.middlewares.0.len(),
+                        request_id: config.request_id,
+                        timeout_ms: route.timeout.or(config.timeout).map(|t| t.as_millis() as u64),
+                        max_concurrent_requests: route.max_concurrent_requests,
+                        auth: config.auth.is_some(),
+                        permissions: endpoint.required_permissions(route),
+                        metrics: config.metrics.is_some(),
+                    },
+                });
+            }
+        }
+        table
+    }
+
+    /// Serves files from given directory under given prefix.
+    ///
+    /// Directories are served with their `index.html` file (requests without a trailing slash are redirected),
+    /// paths escaping the directory and hidden files (i.e. `.env`) are rejected.
+    /// Supports conditional (`ETag` and `Last-Modified`) and single range requests (including `If-Range`).
+    /// Files are read on a small thread pool shared by all requests of given prefix.
+    pub fn static_files<P: Into<PathBuf>>(&mut self, prefix: &str, dir: P) -> Route<'_> {
+        let files = StaticFiles::new(dir);
+        let pattern = format!("{}/{{*path}}", prefix.trim_end_matches('/'));
+        self.get(&*pattern, move |request: Request<params::DynamicParams>| files.serve(&request))
+    }
+
+    /// Returns patterns of all named routes (including the prefixes of composed routers).
+    pub fn urls(&self) -> Urls {
+        let mut urls = Urls::default();
+        for (prefix, endpoint) in self.routes.iter() {
+            let prefix = ::std::str::from_utf8(&prefix).expect("Storing only strings in tree; qed");
+            for (_, params, route) in endpoint.routes() {
+                if let Some(ref name) = route.name {
+                    urls.add(name, route_pattern(prefix, params), &route.param_types);
+                }
+            }
+        }
+        urls
+    }
+
+    /// Builds the path of the route with given name (see `Route::name`).
+    ///
+    /// Handlers can build URLs via `Request::url_for`.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.urls().url_for(name, params)
+    }
+
+    /// Builds the path of the route with given name with params taken from fields of given struct
+    /// (values are validated against types declared with `url!`).
+    pub fn url_for_params<T: Serialize>(&self, name: &str, params: &T) -> Result<String, UrlError> {
+        self.urls().url_for_params(name, params)
+    }
+
+    /// Builds the path of the route with given name with params generated by `url!` (see `UrlParams`).
+    pub fn url_for_typed<T: UrlParams>(&self, name: &str, params: &T) -> Result<String, UrlError> {
+        self.urls().url_for_typed(name, params)
+    }
+
+    /// Generates OpenAPI 3 document describing the endpoints handled by given router.
+    pub fn openapi(&self, title: &str, version: &str) -> serde_json::Value {
+        let mut operations = vec![];
+        for (prefix, endpoint) in self.routes.iter() {
+            let prefix = ::std::str::from_utf8(&prefix).expect("Storing only strings in tree; qed");
+            for (method, params, route) in endpoint.routes() {
+                operations.push(openapi::Operation {
+                    method: method.to_string(),
+                    pattern: route_pattern(prefix, params),
+                    param_types: &route.param_types,
+                    request: route.request_schema.as_ref(),
+                    response: route.response_schema.as_ref(),
+                    permissions: endpoint.required_permissions(route),
+                });
+            }
+        }
+        openapi::document(title, version, operations)
+    }
+
+    /// Serves OpenAPI document (see `openapi`) under given path.
+    ///
+    /// NOTE: Only the routes registered so far are documented,
+    /// so this should be invoked on the top-level router after all the routes are declared.
+    pub fn openapi_route(&mut self, path: &str, title: &str, version: &str) {
+        let document = self.openapi(title, version);
+        self.get(path, move |_: Request<_>| Ok(document.clone()) as Result<_, Error>);
+    }
+
     /// Compose with some other router under given prefix.
+    ///
+    /// # Panics
+    ///
+    /// If both routers have an access log (see `with_access_log`).
     pub fn add(&mut self, prefix: &str, mut router: Router) {
+        if let Some(access_log) = router.access_log.take() {
+            assert!(self.access_log.is_none(), "Only one of the composed routers can have an access log.");
+            self.access_log = Some(access_log);
+        }
+
         let config = self.config.clone();
-        let f = move |endpoint: &mut Endpoint| endpoint.add_config(&config);
+        let states = self.states.clone();
+        let f = move |endpoint: &mut Endpoint| {
+            endpoint.add_config(&config);
+            endpoint.add_states(&states);
+        };
         router.routes.for_each(&f);
 
-        self.routes.merge(prefix, router.routes);
+        let conflicts = &mut self.conflicts;
+        conflicts.extend(router.conflicts.into_iter().map(|conflict| conflict.with_prefix(prefix)));
+        self.routes.merge_with(prefix, router.routes, &mut |key, old, new| {
+            let prefix = ::std::str::from_utf8(key).expect("Storing only strings in tree; qed");
+            conflicts.push(Conflict::Replaced {
+                prefix: prefix.into(),
+                routes: old.routes()
+                    .map(|(method, params, _)| DroppedRoute {
+                        method: method.to_string(),
+                        pattern: route_pattern(prefix, params),
+                    })
+                    .collect(),
+            });
+            new
+        });
+    }
+
+    /// Returns conflicts between routes detected so far
+    /// (shadowed routes, endpoints replaced while composing routers and duplicate route names).
+    ///
+    /// Set `Config::strict_routes` to refuse to start the server if there are any.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = self.conflicts.clone();
+        conflicts.extend(self.urls().duplicate_names().into_iter()
+            .map(|(name, patterns)| Conflict::DuplicateName { name, patterns }));
+        conflicts
     }
 
     /// Consume the router and start HTTP server on given address.
     pub fn bind<T: ::std::net::ToSocketAddrs>(self, address: T) -> Result<Listening, hyper::Error> {
-        let server = Server::new(self.routes);
-        server.bind(address)
+        self.checked_service()?.bind(address)
+    }
+
+    /// Consume the router and return a future serving connections accepted by given listener.
+    ///
+    /// The future needs to be spawned or run on the event loop the listener is bound to.
+    pub fn serve_on(self, handle: &Handle, listener: TcpListener) -> Box<Future<Item = (), Error = ::std::io::Error>> {
+        match self.checked_service() {
+            Ok(server) => server.serve_on(handle, listener),
+            Err(hyper::Error::Io(err)) => Box::new(future::err(err)),
+            Err(err) => Box::new(future::err(::std::io::Error::new(::std::io::ErrorKind::Other, err))),
+        }
+    }
+
+    /// Consume the router and return a future serving connections accepted by given listener
+    /// with given configuration (i.e. connection limits or TLS).
+    ///
+    /// The address and number of threads of the config are ignored.
+    pub fn serve_on_with(self, handle: &Handle, listener: TcpListener, config: ServerConfig) -> Box<Future<Item = (), Error = ::std::io::Error>> {
+        match self.checked_service() {
+            Ok(server) => server.serve_on_with(handle, listener, config),
+            Err(hyper::Error::Io(err)) => Box::new(future::err(err)),
+            Err(err) => Box::new(future::err(::std::io::Error::new(::std::io::ErrorKind::Other, err))),
+        }
+    }
+
+    /// Consume the router and start HTTP server with given configuration
+    /// (i.e. multiple worker threads, connection limits, TLS or Unix socket).
+    pub fn bind_with(self, config: ServerConfig) -> Result<Listening, hyper::Error> {
+        self.checked_service()?.bind_with(config)
+    }
+
+    /// Consume the router and start HTTPS server on given address.
+    pub fn bind_tls<T: ::std::net::ToSocketAddrs>(self, address: T, tls: TlsConfig) -> Result<Listening, hyper::Error> {
+        self.checked_service()?.bind_tls(address, tls)
+    }
+
+    /// Consume the router and start HTTP server on given Unix socket.
+    ///
+    /// Stale socket file left by a previous server is removed.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<::std::path::Path>>(self, path: P) -> Result<Listening, hyper::Error> {
+        self.checked_service()?.bind_unix(path, None)
+    }
+
+    /// Consume the router and start HTTP server on given Unix socket
+    /// with given file permissions (i.e. `0o660`).
+    ///
+    /// The socket is not reachable at the path until the permissions are set.
+    #[cfg(unix)]
+    pub fn bind_unix_with_mode<P: AsRef<::std::path::Path>>(self, path: P, mode: u32) -> Result<Listening, hyper::Error> {
+        self.checked_service()?.bind_unix(path, Some(mode))
+    }
+
+    /// Consume the router and create a `hyper` service handling its routes.
+    ///
+    /// The service can be cloned and used with connections served by your own event loop.
+    ///
+    /// # Panics
+    ///
+    /// If `Config::strict_routes` is set and there are conflicting routes (see `conflicts`).
+    pub fn into_service(self) -> Server {
+        self.checked_service().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates the service unless there are conflicts in strict mode.
+    fn checked_service(self) -> Result<Server, hyper::Error> {
+        let config = self.config.materialize();
+        let conflicts = self.conflicts();
+        if config.strict_routes && !conflicts.is_empty() {
+            let conflicts = conflicts.iter().map(ToString::to_string).collect::<Vec<_>>();
+            return Err(hyper::Error::Io(::std::io::Error::new(
+                ::std::io::ErrorKind::InvalidInput,
+                format!("Conflicting routes:\n  {}", conflicts.join("\n  ")),
+            )));
+        }
+
+        // Make named routes available to handlers.
+        let mut states = States::default();
+        states.push(Arc::new(self.urls()));
+        let mut routes = self.routes;
+        routes.for_each(&|endpoint: &mut Endpoint| endpoint.add_states(&states));
+
+        Ok(Server::new(routes, config).with_access_log(self.access_log))
     }
 
     /// Declare endpoint.
-    pub fn on<'a, F, I, R, E, D, P>(&mut self, method: Method, params: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn on<'a, F, A, I, R, E, D, P>(&mut self, method: Method, params: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -316,28 +1048,52 @@ impl Router {
     {
         let params = params.into();
         let parser = params.parser;
-        let mut endpoint = self.routes.remove(params.prefix).unwrap_or_else(|| Endpoint::with_config(self.config.clone()));
-        let added = endpoint.add(method, parser.expected_params(), Box::new(move |request, prefix_len| {
+        let fun = Arc::new(fun);
+        let param_types = parser.param_types();
+        let mut endpoint = self.routes.remove(params.prefix).unwrap_or_else(|| {
+            Endpoint::with_config(self.config.clone(), self.states.clone())
+        });
+        let expected = parser.expected_params();
+        if let Some(by) = endpoint.shadowed_by(method, &expected) {
+            self.conflicts.push(Conflict::Shadowed {
+                method: method.to_string(),
+                pattern: route_pattern(params.prefix, &expected),
+                by: route_pattern(params.prefix, by),
+            });
+        }
+        let added = endpoint.add(method, expected, Arc::new(move |request: Request, prefix_len| {
+            let request_id = request.request_id().map(ToOwned::to_owned);
             let params = match parser.parse(request.uri(), prefix_len) {
                 Ok(params) => params,
-                Err(err) => return Box::new(future::ok(Error::from(err).into())),
+                Err(err) => return Box::new(future::ok(Error::from(err).with_request_id(request_id).into())),
             };
-            let req = Request::new(request, params);
-            Box::new(fun(req).into_future().then(|result| {
+            let mut req = request.with_params(params);
+            let fun = fun.clone();
+            Box::new(A::from_request(&mut req).and_then(move |args| {
+                fun(args).into_future().map_err(|err| err.into())
+            }).then(|result| {
                 future::ok(match result {
                     Ok(res) => res.into(),
-                    Err(err) => err.into().into(),
+                    Err(err) => err.with_request_id(request_id).into(),
                 }.into())
             }))
         }));
         assert!(added, "The server does not support more than {} handlers for single prefix.", MAX_NUMBER_OF_ENDPOINTS);
         self.routes.insert(params.prefix, endpoint);
+
+        let config = self.routes.get_mut(params.prefix)
+            .and_then(Endpoint::last_route_mut)
+            .expect("The endpoint has just been inserted; qed");
+        config.param_types = param_types;
+        Route { config }
     }
 
 
     /// Declare GET endpoint.
-    pub fn get<'a, F, I, R, E, D, P>(&mut self, prefix: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn get<'a, F, A, I, R, E, D, P>(&mut self, prefix: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -349,8 +1105,10 @@ impl Router {
     }
 
     /// Declare POST endpoint.
-    pub fn post<'a, F, I, R, E, D, P>(&mut self, prefix: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn post<'a, F, A, I, R, E, D, P>(&mut self, prefix: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -362,8 +1120,10 @@ impl Router {
     }
 
     /// Declare PUT endpoint.
-    pub fn put<'a, F, I, R, E, D, P>(&mut self, prefix: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn put<'a, F, A, I, R, E, D, P>(&mut self, prefix: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -375,8 +1135,10 @@ impl Router {
     }
 
     /// Declare PATCH endpoint.
-    pub fn patch<'a, F, I, R, E, D, P>(&mut self, prefix: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn patch<'a, F, A, I, R, E, D, P>(&mut self, prefix: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -388,8 +1150,10 @@ impl Router {
     }
 
     /// Declare DELETE endpoint.
-    pub fn delete<'a, F, I, R, E, D, P>(&mut self, prefix: D, fun: F) where
-        F: Fn(Request<P::Params>) -> I + Sync + Send + 'static,
+    pub fn delete<'a, F, A, I, R, E, D, P>(&mut self, prefix: D, fun: F) -> Route<'_> where
+        F: Fn(A) -> I + Sync + Send + 'static,
+        A: FromRequest<P::Params> + 'static,
+        A::Future: 'static,
         I: IntoFuture<Item = R, Error = E>,
         R: Into<Response>,
         E: Into<Error>,
@@ -400,3 +1164,119 @@ impl Router {
         self.on(Method::Delete, prefix, fun)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+    use error::Error;
+    use request::Request;
+    use testing::TestClient;
+    use super::Router;
+
+    #[test]
+    fn should_describe_route_table() {
+        use std::time::Duration;
+        use super::{ParamInfo, RouteInfo};
+
+        let mut products = Router::with_config(Config::new().require("products:read"));
+        products.get("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>)
+            .timeout(Duration::from_secs(1));
+        let mut router = Router::new();
+        router.add("/v1/products", products);
+
+        let table = router.route_table();
+        assert_eq!(table.len(), 1);
+        let RouteInfo { ref prefix, ref method, ref pattern, ref params, ref config } = table[0];
+        assert_eq!(prefix, "/v1/products/");
+        assert_eq!(method, "GET");
+        assert_eq!(pattern, "/v1/products/{id}");
+        assert_eq!(params, &vec![ParamInfo { name: "id".into(), param_type: "String".into() }]);
+        assert_eq!(config.timeout_ms, Some(1000));
+        assert_eq!(config.permissions, vec!["products:read".to_owned()]);
+
+        let json = ::serde_json::to_value(&table).unwrap();
+        assert_eq!(json[0]["params"], json!([{ "name": "id", "type": "String" }]));
+    }
+
+    #[test]
+    fn should_detect_conflicting_routes() {
+        use super::{Conflict, DroppedRoute};
+
+        let mut products = Router::new();
+        products.get("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>).name("product");
+        products.get("/{name}", |_: Request<_>| Ok("shadowed") as Result<_, Error>);
+        let mut other = Router::new();
+        other.get("/products/", |_: Request<_>| Ok("replaced") as Result<_, Error>);
+        let mut router = Router::with_config(Config::new().strict_routes(true));
+        router.add("/v1", other);
+        router.add("/v1/products", products);
+        router.get("/v2/products/{id}", |_: Request<_>| Ok("product") as Result<_, Error>).name("product");
+
+        assert_eq!(router.conflicts(), vec![
+            Conflict::Shadowed {
+                method: "GET".into(),
+                pattern: "/v1/products/{name}".into(),
+                by: "/v1/products/{id}".into(),
+            },
+            Conflict::Replaced {
+                prefix: "/v1/products/".into(),
+                routes: vec![DroppedRoute { method: "GET".into(), pattern: "/v1/products/".into() }],
+            },
+            Conflict::DuplicateName {
+                name: "product".into(),
+                patterns: vec!["/v1/products/{id}".into(), "/v2/products/{id}".into()],
+            },
+        ]);
+        let error = router.bind("127.0.0.1:0").unwrap_err().to_string();
+        assert!(error.contains("GET /v1/products/{name} is shadowed by GET /v1/products/{id}"), "{}", error);
+        assert!(error.contains("Endpoint /v1/products/ was replaced, dropped routes: GET /v1/products/"), "{}", error);
+    }
+
+    #[test]
+    fn should_detect_routes_shadowed_by_catch_all() {
+        let mut router = Router::new();
+        router.get("/files/{*path}", |_: Request<_>| Ok("file") as Result<_, Error>);
+        router.get("/files/{dir}/{name}", |_: Request<_>| Ok("shadowed") as Result<_, Error>);
+        router.get("/files/{*rest}", |_: Request<_>| Ok("shadowed") as Result<_, Error>);
+        router.post("/files/{dir}/{name}", |_: Request<_>| Ok("other method") as Result<_, Error>);
+        router.post("/files/{*path}", |_: Request<_>| Ok("longer paths") as Result<_, Error>);
+
+        let conflicts = router.conflicts().iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(conflicts, vec![
+            "GET /files/{dir}/{name} is shadowed by GET /files/{*path}".to_owned(),
+            "GET /files/{*rest} is shadowed by GET /files/{*path}".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn should_build_urls_of_named_routes() {
+        use {parser, printer, url, url_internal};
+        use urls::UrlError;
+
+        #[derive(Serialize)]
+        struct ProductPath {
+            id: u64,
+        }
+
+        let product = url!(/{id:u64});
+        let link = product.parser.clone();
+        let mut products = Router::new();
+        products.get(product, |request: Request<_>| request.url_for_typed("product", request.params())).name("product");
+        products.post("/", |request: Request<_>| {
+            request.url_for("product", &[("id", "5")])
+        }).name("products");
+        let mut router = Router::new();
+        router.add("/v1/products", products);
+        assert!(router.conflicts().is_empty());
+
+        assert_eq!(router.url_for("product", &[("id", "5")]), Ok("/v1/products/5".into()));
+        assert_eq!(router.url_for("products", &[]), Ok("/v1/products/".into()));
+        assert_eq!(router.url_for_params("product", &ProductPath { id: 7 }), Ok("/v1/products/7".into()));
+        assert_eq!(router.url_for_typed("product", &link.id(9)), Ok("/v1/products/9".into()));
+        assert_eq!(router.url_for("product", &[]), Err(UrlError::MissingParam("id".into())));
+
+        let client = TestClient::new(router);
+        assert_eq!(client.post("/v1/products/").send().text(), "\"/v1/products/5\"");
+        assert_eq!(client.get("/v1/products/3").send().text(), "\"/v1/products/3\"");
+    }
+}
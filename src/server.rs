@@ -1,60 +1,1095 @@
-use std::sync::Arc;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{self as unix, UnixListener};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::process;
 use hyper;
-use futures::future;
+use futures::{future, Async, Future, Poll, Stream};
+use futures::sync::oneshot;
+use net2::TcpBuilder;
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::{Core, Handle};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+#[cfg(unix)]
+use tokio_uds::{self, UnixStream};
 
+use access_log::{self, AccessLog, Entry};
+use concurrency::{Guard, Limiter};
+use config::MaterializedConfig;
 use error::Error;
+use metrics;
+use request::{Extensions, Request, RequestId};
 use router::{Routes, HandlerResult};
+use timeout;
+use tls::{Acceptor, TlsConfig};
 
+/// Time to wait before accepting connections again after the listener fails.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Handler of listener errors (see `ServerConfig::on_accept_error`).
+#[derive(Clone)]
+pub(crate) struct AcceptErrorHook(Arc<Fn(&io::Error) + Send + Sync>);
+
+impl fmt::Debug for AcceptErrorHook {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "AcceptErrorHook")
+    }
+}
+
+/// A connection accepted by the server.
+pub(crate) trait Connection: AsyncRead + AsyncWrite + 'static {
+    /// Adds connection details (i.e. peer certificate) to extensions of every request.
+    fn extensions(&self, _extensions: &mut Extensions) {}
+}
+
+impl Connection for TcpStream {}
+
+#[cfg(unix)]
+impl Connection for UnixStream {}
+
+/// Future resolving to an accepted connection (i.e. after TLS handshake)
+/// and the address of the peer (if it has one).
+pub(crate) type Accept<C> = Box<Future<Item = (C, Option<SocketAddr>), Error = io::Error>>;
+
+/// A `hyper` service handling requests with the router (see `Router::into_service`).
+///
+/// Cloning the server is cheap, the routes are shared.
 #[derive(Clone)]
 pub struct Server {
-    pub routes: Arc<Routes>,
+    routes: Arc<Routes>,
+    config: Arc<MaterializedConfig>,
+    access_log: Option<Arc<AccessLog>>,
+    limiter: Limiter,
+    connection: Extensions,
+    // Released when hyper drops the service after the connection is closed.
+    connection_guard: Option<Arc<Guard>>,
 }
 
 impl Server {
-    pub fn new(routes: Routes) -> Self {
+    pub(crate) fn new(routes: Routes, config: MaterializedConfig) -> Self {
         Server {
             routes: Arc::new(routes),
+            limiter: Limiter::new(config.max_concurrent_requests, Default::default()),
+            config: Arc::new(config),
+            access_log: None,
+            connection: Default::default(),
+            connection_guard: None,
+        }
+    }
+
+    pub(crate) fn with_access_log(mut self, access_log: Option<Arc<AccessLog>>) -> Self {
+        self.access_log = access_log;
+        self
+    }
+
+    /// Starts HTTP server on given address.
+    pub fn bind<T: ToSocketAddrs>(self, address: T) -> Result<Listening, hyper::Error> {
+        self.bind_with(ServerConfig::new(socket_addr(address)?))
+    }
+
+    /// Starts HTTP server with given configuration.
+    pub fn bind_with(self, mut config: ServerConfig) -> Result<Listening, hyper::Error> {
+        let tls = match config.tls.take() {
+            Some(tls) => Some(tls.build()?),
+            None => None,
+        };
+        match config.address.clone() {
+            Address::Tcp(address) => self.bind_tcp(&config, address, tls),
+            #[cfg(unix)]
+            Address::Unix(path) => self.bind_unix_socket(&config, path, tls),
+        }
+    }
+
+    /// Starts HTTPS server on given address.
+    pub fn bind_tls<T: ToSocketAddrs>(self, address: T, tls: TlsConfig) -> Result<Listening, hyper::Error> {
+        self.bind_with(ServerConfig::new(socket_addr(address)?).tls(tls))
+    }
+
+    /// Starts HTTP server on given Unix socket (optionally setting the socket file permissions).
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(self, path: P, mode: Option<u32>) -> Result<Listening, hyper::Error> {
+        let config = ServerConfig::unix(path);
+        self.bind_with(match mode {
+            Some(mode) => config.mode(mode),
+            None => config,
+        })
+    }
+
+    fn bind_tcp(self, config: &ServerConfig, address: SocketAddr, tls: Option<Acceptor>) -> Result<Listening, hyper::Error> {
+        let listener = tcp_listener(&address, config.reuse_port)?;
+        let address = listener.local_addr()?;
+        let mut listeners = vec![];
+        for _ in 1..config.threads {
+            listeners.push(if config.reuse_port {
+                tcp_listener(&address, true)?
+            } else {
+                listener.try_clone()?
+            });
+        }
+        listeners.push(listener);
+
+        let http = config.http();
+        let connections = Limiter::new(config.max_connections, Default::default());
+        let workers = listeners.into_iter().map(|listener| {
+            let (server, http, connections, tls) = (self.clone(), http.clone(), connections.clone(), tls.clone());
+            let on_accept_error = config.on_accept_error.clone();
+            move |handle: &Handle| {
+                let incoming = TcpListener::from_listener(listener, &address, handle)?.incoming()
+                    .map(|(io, address)| (io, Some(address)));
+                Ok(server.serve_with(handle, incoming, tls.as_ref(), &http, &connections, on_accept_error.as_ref()))
+            }
+        }).collect();
+        Ok(Listening::spawn(Some(address), workers)?)
+    }
+
+    #[cfg(unix)]
+    fn bind_unix_socket(self, config: &ServerConfig, path: PathBuf, tls: Option<Acceptor>) -> Result<Listening, hyper::Error> {
+        remove_stale_socket(&path)?;
+        let listener = unix_listener(&path, config.mode)?;
+        let http = config.http();
+        let connections = Limiter::new(config.max_connections, Default::default());
+        let listening = (1..config.threads).map(|_| listener.try_clone()).collect::<io::Result<Vec<_>>>()
+            .and_then(|mut listeners| {
+                listeners.push(listener);
+                let workers = listeners.into_iter().map(|listener| {
+                    let (server, http, connections, tls) = (self.clone(), http.clone(), connections.clone(), tls.clone());
+                    let on_accept_error = config.on_accept_error.clone();
+                    move |handle: &Handle| {
+                        let incoming = tokio_uds::UnixListener::from_listener(listener, handle)?.incoming()
+                            .map(|(io, _)| (io, None));
+                        Ok(server.serve_with(handle, incoming, tls.as_ref(), &http, &connections, on_accept_error.as_ref()))
+                    }
+                }).collect();
+                Listening::spawn(None, workers)
+            });
+
+        match listening {
+            Ok(mut listening) => {
+                listening.path = Some(path);
+                Ok(listening)
+            },
+            Err(err) => {
+                let _ = fs::remove_file(&path);
+                Err(err.into())
+            },
+        }
+    }
+
+    /// Returns a future serving connections accepted by given listener.
+    ///
+    /// The future needs to be spawned or run on the event loop the listener is bound to.
+    pub fn serve_on(self, handle: &Handle, listener: TcpListener) -> Box<Future<Item = (), Error = io::Error>> {
+        let incoming = listener.incoming().map(|(io, address)| (io, Some(address)));
+        self.serve_with(handle, incoming, None, &hyper::server::Http::new(), &Limiter::new(None, Default::default()), None)
+    }
+
+    /// Returns a future serving connections accepted by given listener with given configuration.
+    ///
+    /// Only connection settings of the config are used (keep-alive, pipelining, connection limit, TLS
+    /// and accept error handler),
+    /// the address and number of threads are ignored.
+    pub fn serve_on_with(self, handle: &Handle, listener: TcpListener, config: ServerConfig) -> Box<Future<Item = (), Error = io::Error>> {
+        let tls = match config.tls.clone().map(TlsConfig::build) {
+            Some(Ok(tls)) => Some(tls),
+            Some(Err(err)) => return Box::new(future::err(err)),
+            None => None,
+        };
+        let incoming = listener.incoming().map(|(io, address)| (io, Some(address)));
+        let connections = Limiter::new(config.max_connections, Default::default());
+        self.serve_with(handle, incoming, tls.as_ref(), &config.http(), &connections, config.on_accept_error.as_ref())
+    }
+
+    /// Serves connections accepted from given stream (performing TLS handshake if `tls` is set).
+    fn serve_with<S, C>(
+        self,
+        handle: &Handle,
+        incoming: S,
+        tls: Option<&Acceptor>,
+        http: &hyper::server::Http<hyper::Chunk>,
+        connections: &Limiter,
+        on_accept_error: Option<&AcceptErrorHook>,
+    ) -> Box<Future<Item = (), Error = io::Error>> where
+        S: Stream<Item = (C, Option<SocketAddr>), Error = io::Error> + 'static,
+        C: Connection,
+    {
+        match tls {
+            Some(tls) => {
+                let tls = tls.clone();
+                let incoming = incoming.map(move |(io, address)| Box::new(tls.accept(io).map(move |io| (io, address))) as Accept<_>);
+                self.serve(handle, incoming, http, connections, on_accept_error)
+            },
+            None => {
+                let incoming = incoming.map(|(io, address)| Box::new(future::ok((io, address))) as Accept<_>);
+                self.serve(handle, incoming, http, connections, on_accept_error)
+            },
         }
     }
 
-    pub fn bind<T: ::std::net::ToSocketAddrs>(self, address: T) -> Result<Listening, hyper::Error> {
-        // TODO handle errors
-        let address = address.to_socket_addrs()?.next().unwrap();
-        let server = hyper::server::Http::new().bind(&address, move || Ok(self.clone()))?;
-        server.run()?;
-        unimplemented!()
+    /// Serves connections accepted from given stream.
+    ///
+    /// Failed accepts (i.e. TLS handshake errors) only drop the connection,
+    /// so do connections exceeding the limit.
+    /// Other errors of the listener (i.e. too many open files) are passed to `on_accept_error`
+    /// and accepting is paused for a while.
+    pub(crate) fn serve<S, C>(
+        self,
+        handle: &Handle,
+        incoming: S,
+        http: &hyper::server::Http<hyper::Chunk>,
+        connections: &Limiter,
+        on_accept_error: Option<&AcceptErrorHook>,
+    ) -> Box<Future<Item = (), Error = io::Error>> where
+        S: Stream<Item = Accept<C>, Error = io::Error> + 'static,
+        C: Connection,
+    {
+        let (http, connections, on_accept_error) = (http.clone(), connections.clone(), on_accept_error.cloned());
+        let handle = handle.clone();
+        let incoming = incoming.then(move |result| recover_accept(result, on_accept_error.as_ref()));
+        Box::new(incoming.filter_map(|accept| accept).for_each(move |accept| {
+            let guard = match connections.acquire() {
+                Some(guard) => guard,
+                None => return Ok(()),
+            };
+            let mut server = self.clone();
+            server.connection_guard = Some(Arc::new(guard));
+            let http = http.clone();
+            let h = handle.clone();
+            handle.spawn(accept.then(move |result| {
+                let (io, address) = match result {
+                    Ok(connection) => connection,
+                    Err(_) => return Ok(()),
+                };
+                let mut server = server;
+                io.extensions(&mut server.connection);
+                let service = ConnectionService(server);
+                match address {
+                    // Makes the peer address available via `Request::remote_addr`.
+                    #[allow(deprecated)]
+                    Some(address) => http.bind_connection(&h, io, address, service),
+                    None => h.spawn(http.serve_connection(io, service).then(|_| Ok(()))),
+                }
+                Ok(())
+            }));
+            Ok(())
+        }))
     }
 }
 
+/// Skips connections that failed to be accepted, pausing after errors of the listener.
+fn recover_accept<C: 'static>(
+    result: io::Result<Accept<C>>,
+    on_error: Option<&AcceptErrorHook>,
+) -> Box<Future<Item = Option<Accept<C>>, Error = io::Error>> {
+    match result {
+        Ok(accept) => Box::new(future::ok(Some(accept))),
+        // The peer has gone away before we accepted the connection.
+        Err(ref err) if err.kind() == io::ErrorKind::ConnectionAborted || err.kind() == io::ErrorKind::ConnectionReset => {
+            Box::new(future::ok(None))
+        },
+        Err(err) => {
+            if let Some(on_error) = on_error {
+                (on_error.0)(&err);
+            }
+            Box::new(Delay::new(Instant::now() + ACCEPT_BACKOFF).then(|_| Ok(None)))
+        },
+    }
+}
+
+/// Response with slots of concurrency limits held by the request.
+type DispatchResult = Box<Future<Item = (hyper::Response, Vec<Guard>), Error = hyper::Error>>;
+
 impl hyper::server::Service for Server {
     type Request = hyper::Request;
     type Response = hyper::Response;
     type Error = hyper::Error;
     type Future = HandlerResult;
 
+    /// Handles the request.
+    ///
+    /// NOTE: The request stops counting towards concurrency limits once the response head is produced.
+    /// Connections served by `Listening` keep it counted until the body is sent.
     fn call(&self, req: Self::Request) -> Self::Future {
+        Box::new(self.dispatch(req).map(|(response, _guards)| response))
+    }
+}
+
+impl Server {
+    fn dispatch(&self, req: hyper::Request) -> DispatchResult {
         let path = req.uri().path().to_owned();
         let method = req.method().into();
-        match self.routes.find(path) {
-            Some((prefix, ref endpoint)) => {
-                endpoint.handle(method, req, prefix)
+        let request_id = if self.config.request_id {
+            let header = &self.config.request_id_header;
+            let id = req.headers().get_raw(header)
+                .and_then(|raw| raw.one())
+                .and_then(|id| ::std::str::from_utf8(id).ok())
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(generate_request_id);
+            Some((header.clone(), id))
+        } else {
+            None
+        };
+
+        // Peer address is still set by hyper when serving connections.
+        #[allow(deprecated)]
+        let peer = req.remote_addr();
+        let log = self.access_log.as_ref().map(|log| {
+            let path = match req.uri().query() {
+                Some(query) => format!("{}?{}", req.uri().path(), query),
+                None => req.uri().path().to_owned(),
+            };
+            let entry = Entry {
+                timestamp: access_log::timestamp(SystemTime::now()),
+                peer,
+                method: req.method().to_string(),
+                path,
+                version: req.version().to_string(),
+                route: None,
+                status: 0,
+                size: None,
+                latency_ms: 0.0,
+            };
+            (log.clone(), entry, Instant::now())
+        });
+
+        let mut request = Request::new(req, ());
+        *request.extensions_mut() = self.connection.clone();
+        if let Some((_, ref id)) = request_id {
+            request.extensions_mut().insert(RequestId(id.clone()));
+        }
+
+        let mut route = None;
+        let response: DispatchResult = match (self.limiter.process(), self.routes.find(&path)) {
+            (Ok(guard), Some((prefix, ref endpoint))) => {
+                if log.is_some() {
+                    route = endpoint.route_pattern(method, &path, prefix);
+                }
+                Box::new(endpoint.handle(method, request, prefix).map(move |(response, mut guards)| {
+                    guards.push(guard);
+                    (response, guards)
+                }))
+            },
+            (Ok(guard), None) => {
+                if let Some(ref metrics) = self.config.metrics {
+                    metrics.observe(&method.to_string(), metrics::UNMATCHED_ROUTE, 404, Duration::default());
+                }
+                let error = Error::not_found("Requested resource was not found.");
+                Box::new(future::ok((
+                    error.with_request_id(request_id.as_ref().map(|id| id.1.clone())).into(),
+                    vec![guard],
+                )))
             },
-            None => Box::new(future::ok(Error::not_found(
-                "Requested resource was not found."
-            ).into())),
+            (Err(error), _) => Box::new(future::ok((
+                error.with_request_id(request_id.as_ref().map(|id| id.1.clone())).into(),
+                vec![],
+            ))),
+        };
+
+        let response = match request_id {
+            None => response,
+            Some((header, id)) => Box::new(response.map(move |(mut response, guards)| {
+                response.headers_mut().set_raw(header, id);
+                (response, guards)
+            })),
+        };
+
+        match log {
+            None => response,
+            Some((log, mut entry, started)) => Box::new(response.map(move |(response, guards)| {
+                entry.route = route;
+                entry.status = response.status().as_u16();
+                entry.size = response.headers().get::<hyper::header::ContentLength>().map(|len| len.0);
+                entry.latency_ms = timeout::secs(started.elapsed()) * 1_000.0;
+                log.log(&entry);
+                (response, guards)
+            })),
+        }
+    }
+}
+
+/// Service handling connections accepted by the server.
+///
+/// Responses keep their concurrency slots until the body is sent.
+struct ConnectionService(Server);
+
+impl hyper::server::Service for ConnectionService {
+    type Request = hyper::Request;
+    type Response = hyper::Response<ResponseBody>;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        Box::new(self.0.dispatch(req).map(|(mut response, guards)| {
+            let headers = mem::replace(response.headers_mut(), hyper::Headers::new());
+            hyper::Response::new()
+                .with_status(response.status())
+                .with_headers(headers)
+                .with_body(ResponseBody {
+                    body: response.body(),
+                    guards,
+                })
+        }))
+    }
+}
+
+/// Response body releasing concurrency slots once it's fully sent (or dropped).
+struct ResponseBody {
+    body: hyper::Body,
+    guards: Vec<Guard>,
+}
+
+impl Stream for ResponseBody {
+    type Item = hyper::Chunk;
+    type Error = hyper::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let result = self.body.poll();
+        match result {
+            Ok(Async::NotReady) | Ok(Async::Ready(Some(_))) => {},
+            Ok(Async::Ready(None)) | Err(_) => self.guards.clear(),
         }
+        result
     }
 }
 
+/// Address the server listens on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// HTTP server configuration (see `Router::bind_with`).
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    address: Address,
+    tls: Option<TlsConfig>,
+    mode: Option<u32>,
+    threads: usize,
+    reuse_port: bool,
+    keep_alive: bool,
+    pipeline: bool,
+    max_connections: Option<usize>,
+    on_accept_error: Option<AcceptErrorHook>,
+}
+
+impl ServerConfig {
+    /// Creates a config of a single-threaded server listening on given address.
+    pub fn new(address: SocketAddr) -> Self {
+        Self::with_address(Address::Tcp(address))
+    }
+
+    /// Creates a config of a single-threaded server listening on given Unix socket.
+    ///
+    /// Stale socket file left by a previous server is removed.
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_address(Address::Unix(path.as_ref().to_owned()))
+    }
+
+    fn with_address(address: Address) -> Self {
+        ServerConfig {
+            address,
+            tls: None,
+            mode: None,
+            threads: 1,
+            reuse_port: false,
+            keep_alive: true,
+            pipeline: false,
+            max_connections: None,
+            on_accept_error: None,
+        }
+    }
+
+    /// Serve connections over TLS.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Permissions of the socket file (i.e. `0o660`), Unix sockets only.
+    ///
+    /// The socket is not reachable at the path until the permissions are set.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Number of worker threads, each running its own event loop.
+    ///
+    /// Workers accept connections from a shared listener unless `reuse_port` is enabled.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "At least one worker thread is required.");
+        self.threads = threads;
+        self
+    }
+
+    /// Set to true to bind a separate listener for every worker with `SO_REUSEPORT`,
+    /// so that the kernel balances connections between workers (Unix only).
+    pub fn reuse_port(mut self, reuse_port: bool) -> Self {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    /// Set to false to close connections after every response. Enabled by default.
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Set to true to aggregate writes of pipelined responses. Disabled by default.
+    pub fn pipeline(mut self, pipeline: bool) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Maximal number of open connections (for all workers).
+    ///
+    /// Connections over the limit are closed right after being accepted.
+    pub fn max_connections<T: Into<Option<usize>>>(mut self, max: T) -> Self {
+        self.max_connections = max.into();
+        self
+    }
+
+    /// Called with errors of the listener (i.e. too many open files), which are ignored by default.
+    ///
+    /// Accepting connections is paused for a while after every such error.
+    pub fn on_accept_error<F>(mut self, on_error: F) -> Self where
+        F: Fn(&io::Error) + Send + Sync + 'static,
+    {
+        self.on_accept_error = Some(AcceptErrorHook(Arc::new(on_error)));
+        self
+    }
+
+    fn http(&self) -> hyper::server::Http<hyper::Chunk> {
+        let mut http = hyper::server::Http::new();
+        http.keep_alive(self.keep_alive).pipeline(self.pipeline);
+        http
+    }
+}
+
+/// Resolves the address to bind to.
+fn socket_addr<T: ToSocketAddrs>(address: T) -> io::Result<SocketAddr> {
+    address.to_socket_addrs()?.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No address to bind to."))
+}
+
+/// Binds TCP listener (optionally with `SO_REUSEADDR` and `SO_REUSEPORT`).
+fn tcp_listener(address: &SocketAddr, reuse_port: bool) -> io::Result<net::TcpListener> {
+    if !reuse_port {
+        return net::TcpListener::bind(address);
+    }
+
+    let builder = match *address {
+        SocketAddr::V4(_) => TcpBuilder::new_v4()?,
+        SocketAddr::V6(_) => TcpBuilder::new_v6()?,
+    };
+    builder.reuse_address(true)?;
+    #[cfg(unix)]
+    {
+        use net2::unix::UnixTcpBuilderExt;
+        builder.reuse_port(true)?;
+    }
+    builder.bind(address)?;
+    builder.listen(1024)
+}
+
+/// Removes the socket file left by a server that is no longer running.
+///
+/// Fails if the file is not a socket or the server is still accepting connections.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let in_use = || io::Error::new(io::ErrorKind::AddrInUse, format!("{} is already in use.", path.display()));
+    if !metadata.file_type().is_socket() {
+        return Err(in_use());
+    }
+    match unix::UnixStream::connect(path) {
+        Ok(_) => Err(in_use()),
+        Err(ref err) if err.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(unix)]
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Binds Unix socket at given path.
+///
+/// If `mode` is set, the socket is bound in a private directory first
+/// and moved to the path only after its permissions are set,
+/// so that it's never reachable with the default ones.
+#[cfg(unix)]
+fn unix_listener(path: &Path, mode: Option<u32>) -> io::Result<UnixListener> {
+    let mode = match mode {
+        Some(mode) => mode,
+        None => return UnixListener::bind(path),
+    };
+
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let dir = parent.join(format!(".resty-{}-{}", process::id(), SOCKET_COUNTER.fetch_add(1, Ordering::Relaxed)));
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let tmp = dir.join("s");
+    let listener = UnixListener::bind(&tmp)
+        .and_then(|listener| fs::set_permissions(&tmp, fs::Permissions::from_mode(mode)).map(|_| listener))
+        .and_then(|listener| fs::rename(&tmp, path).map(|_| listener));
+    if listener.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    let _ = fs::remove_dir(&dir);
+    listener
+}
+
+static REQUEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Generates unique request id.
+fn generate_request_id() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let counter = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}{:08x}-{:x}", process::id(), now.as_secs(), now.subsec_nanos(), counter)
+}
+
 /// Resty Server Handle
+///
+/// The server runs in background threads and is shut down when the handle is dropped,
+/// so keep it around (i.e. call `Listening::wait`) for as long as the server should run.
+/// Unix socket file is removed after the server is shut down.
 #[derive(Debug)]
+#[must_use = "the server is shut down when `Listening` is dropped"]
 pub struct Listening {
-
+    address: Option<SocketAddr>,
+    path: Option<PathBuf>,
+    shutdown: Vec<oneshot::Sender<()>>,
+    threads: Vec<thread::JoinHandle<io::Result<()>>>,
 }
 
 impl Listening {
+    /// Runs every worker on a new thread with its own event loop.
+    pub(crate) fn spawn<F>(address: Option<SocketAddr>, workers: Vec<F>) -> io::Result<Self> where
+        F: FnOnce(&Handle) -> io::Result<Box<Future<Item = (), Error = io::Error>>> + Send + 'static,
+    {
+        let mut listening = Listening {
+            address,
+            path: None,
+            shutdown: vec![],
+            threads: vec![],
+        };
+
+        for (id, serve) in workers.into_iter().enumerate() {
+            let (started_tx, started_rx) = mpsc::channel();
+            let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+            let thread = thread::Builder::new().name(format!("resty-server-{}", id)).spawn(move || {
+                let started = Core::new().and_then(|core| {
+                    let server = serve(&core.handle())?;
+                    Ok((core, server))
+                });
+                let (mut core, server) = match started {
+                    Ok(started) => {
+                        let _ = started_tx.send(Ok(()));
+                        started
+                    },
+                    Err(err) => {
+                        let _ = started_tx.send(Err(err));
+                        return Ok(());
+                    },
+                };
+                // Dropping the sender also shuts down the server.
+                let shutdown = shutdown_rx.then(|_| Ok(()));
+                core.run(server.select(shutdown).map(|_| ()).map_err(|(err, _)| err))
+            })?;
+            listening.shutdown.push(shutdown);
+            listening.threads.push(thread);
+
+            // Workers that have already started are shut down when `listening` is dropped.
+            match started_rx.recv() {
+                Ok(Ok(())) => {},
+                Ok(Err(err)) => return Err(err),
+                Err(_) => return Err(io::Error::new(io::ErrorKind::Other, "Server thread panicked.")),
+            }
+        }
+
+        Ok(listening)
+    }
+
+    /// Returns the address the server is listening on (for TCP servers).
+    pub fn addr(&self) -> Option<&SocketAddr> {
+        self.address.as_ref()
+    }
+
+    /// Returns the path of the socket the server is listening on (for Unix socket servers).
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref().map(AsRef::as_ref)
+    }
+
     /// Block the thread waiting for the server to finish.
-    pub fn wait(self) {
-        unimplemented!()
+    pub fn wait(mut self) {
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+
+    /// Stops accepting new connections and waits for the server threads to finish.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        for shutdown in self.shutdown.drain(..) {
+            let _ = shutdown.send(());
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        if let Some(ref path) = self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use futures::{Future, Stream};
+    use hyper;
+    use hyper::server::Service;
+    use serde_json;
+
+    use access_log::{AccessLog, Format};
+    use access_log::tests::Sink;
+    use config::Config;
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use super::Server;
+
+    fn call(server: &Server, path: &str, id: Option<&str>) -> (hyper::StatusCode, hyper::Headers, serde_json::Value) {
+        let mut request = hyper::Request::new(hyper::Method::Get, path.parse().unwrap());
+        if let Some(id) = id {
+            request.headers_mut().set_raw("X-Trace-Id", id);
+        }
+        let response = server.call(request).wait().unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = response.body().concat2().wait().unwrap();
+        (status, headers, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn should_propagate_request_id() {
+        let config = Config::new().request_id(true).request_id_header("X-Trace-Id".to_owned());
+        let mut router = Router::with_config(config);
+        router.get("/id", |request: Request<_>| Ok(request.request_id().map(ToOwned::to_owned)) as Result<_, Error>);
+        router.get("/error", |_: Request<_>| Err(Error::bad_request("Error", "")) as Result<(), _>);
+        let server = router.into_service();
+
+        let (_, headers, body) = call(&server, "/id", Some("abc"));
+        assert_eq!(headers.get_raw("X-Trace-Id").unwrap(), "abc");
+        assert_eq!(body, json!("abc"));
+
+        let (_, headers, body) = call(&server, "/id", None);
+        let generated = body.as_str().unwrap().to_owned();
+        assert!(!generated.is_empty());
+        assert_eq!(headers.get_raw("X-Trace-Id").unwrap(), &*generated);
+
+        let (_, _, body) = call(&server, "/error", Some("def"));
+        assert_eq!(body["request_id"], json!("def"));
+
+        let (status, _, body) = call(&server, "/unknown", Some("ghi"));
+        assert_eq!(status, hyper::StatusCode::NotFound);
+        assert_eq!(body["request_id"], json!("ghi"));
+    }
+
+    #[test]
+    fn should_log_requests() {
+        let sink = Sink::default();
+        let mut products = Router::new();
+        products.get("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>);
+        let mut router = Router::new().with_access_log(AccessLog::new(Format::Json, sink.clone()));
+        router.add("/products", products);
+        let server = router.into_service();
+
+        call(&server, "/products/5?x=1", None);
+        call(&server, "/unknown", None);
+
+        let output = sink.output();
+        let entries = output.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["method"], json!("GET"));
+        assert_eq!(entries[0]["path"], json!("/products/5?x=1"));
+        assert_eq!(entries[0]["route"], json!("/products/{id}"));
+        assert_eq!(entries[0]["status"], json!(200));
+        assert_eq!(entries[0]["size"], json!(9));
+        assert_eq!(entries[1]["route"], json!(null));
+        assert_eq!(entries[1]["status"], json!(404));
+    }
+
+    #[test]
+    fn should_log_requests_with_log_of_nested_router() {
+        let sink = Sink::default();
+        let mut products = Router::new().with_access_log(AccessLog::new(Format::Json, sink.clone()));
+        products.get("/{id}", |_: Request<_>| Ok("product") as Result<_, Error>);
+        let mut router = Router::new();
+        router.add("/products", products);
+        let server = router.into_service();
+
+        call(&server, "/products/5", None);
+        call(&server, "/unknown", None);
+
+        assert_eq!(sink.output().lines().count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only one of the composed routers can have an access log.")]
+    fn should_refuse_composing_routers_with_access_logs() {
+        let products = Router::new().with_access_log(AccessLog::new(Format::Json, Sink::default()));
+        let mut router = Router::new().with_access_log(AccessLog::new(Format::Json, Sink::default()));
+        router.add("/products", products);
+    }
+
+    #[test]
+    fn should_shed_load_over_concurrency_limit() {
+        use futures::future;
+
+        let mut router = Router::with_config(Config::new().max_concurrent_requests(1));
+        router.get("/slow", |_: Request<_>| future::empty::<(), Error>());
+        router.get("/fast", |_: Request<_>| Ok(()) as Result<_, Error>);
+        let server = router.into_service();
+        let request = |path: &str| hyper::Request::new(hyper::Method::Get, path.parse().unwrap());
+
+        let pending = server.call(request("/slow"));
+        let response = server.call(request("/fast")).wait().unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::ServiceUnavailable);
+        assert!(response.headers().get::<hyper::header::RetryAfter>().is_some());
+
+        drop(pending);
+        let (status, _, _) = call(&server, "/fast", None);
+        assert_eq!(status, hyper::StatusCode::Ok);
+    }
+
+    #[test]
+    fn should_hold_route_limit_until_body_is_sent() {
+        use std::sync::Mutex;
+        use response::Response;
+        use super::ConnectionService;
+
+        let senders = Arc::new(Mutex::new(vec![]));
+        let s = senders.clone();
+        let mut router = Router::new();
+        router.get("/stream", move |_: Request<_>| {
+            let (sender, body) = hyper::Body::pair();
+            s.lock().unwrap().push(sender);
+            Ok(Response::raw(hyper::Response::new().with_body(body))) as Result<_, Error>
+        }).max_concurrent_requests(1);
+        router.get("/other", |_: Request<_>| Ok(()) as Result<_, Error>);
+        let service = ConnectionService(router.into_service());
+        let request = |path: &str| hyper::Request::new(hyper::Method::Get, path.parse().unwrap());
+
+        let streaming = service.call(request("/stream")).wait().unwrap();
+        assert_eq!(streaming.status(), hyper::StatusCode::Ok);
+        assert_eq!(service.call(request("/stream")).wait().unwrap().status(), hyper::StatusCode::ServiceUnavailable);
+        assert_eq!(service.call(request("/other")).wait().unwrap().status(), hyper::StatusCode::Ok);
+
+        drop(streaming);
+        assert_eq!(service.call(request("/stream")).wait().unwrap().status(), hyper::StatusCode::Ok);
+    }
+
+    #[test]
+    fn should_release_slots_at_the_end_of_body() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use concurrency::Guard;
+        use super::ResponseBody;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let mut body = ResponseBody {
+            body: "body".into(),
+            guards: vec![Guard::new(&in_flight)],
+        };
+        assert_eq!(in_flight.load(Ordering::SeqCst), 1);
+        assert_eq!(&*(&mut body).concat2().wait().unwrap(), &b"body"[..]);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_serve_on_unix_socket() {
+        use std::fs;
+        use std::io::{Read, Write};
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let path = ::std::env::temp_dir().join(format!("resty-{}.sock", ::std::process::id()));
+        // Leave a stale socket file behind.
+        drop(UnixListener::bind(&path).unwrap());
+
+        let mut router = Router::new();
+        router.get("/hello", |_: Request<_>| Ok("unix") as Result<_, Error>);
+        let listening = router.bind_unix_with_mode(&path, 0o600).unwrap();
+        assert_eq!(listening.path(), Some(&*path));
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        // The directory the socket was bound in is removed.
+        let prefix = format!(".resty-{}-", ::std::process::id());
+        assert!(!fs::read_dir(path.parent().unwrap()).unwrap()
+            .any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with(&prefix)));
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\"unix\""), "{}", response);
+
+        let mut other = Router::new();
+        other.get("/hello", |_: Request<_>| Ok("other") as Result<_, Error>);
+        assert!(other.bind_unix(&path).is_err());
+
+        drop(listening);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn should_limit_connections_of_multithreaded_server() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::thread;
+        use std::time::Duration;
+        use server::ServerConfig;
+
+        let mut router = Router::new();
+        router.get("/hello", |_: Request<_>| Ok("hello") as Result<_, Error>);
+        let config = ServerConfig::new("127.0.0.1:0".parse().unwrap()).threads(2).max_connections(1);
+        let listening = router.bind_with(config).unwrap();
+        let address = *listening.addr().unwrap();
+
+        let get = |stream: &mut TcpStream| -> String {
+            let mut response = vec![0u8; 1024];
+            let _ = stream.write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            let read = stream.read(&mut response).unwrap_or(0);
+            String::from_utf8_lossy(&response[..read]).into_owned()
+        };
+
+        let mut first = TcpStream::connect(address).unwrap();
+        assert!(get(&mut first).ends_with("\"hello\""));
+        // Over the limit, the connection is closed.
+        assert_eq!(get(&mut TcpStream::connect(address).unwrap()), "");
+        // Keep-alive connection is still open.
+        assert!(get(&mut first).ends_with("\"hello\""));
+
+        drop(first);
+        let mut response = String::new();
+        for _ in 0..50 {
+            response = get(&mut TcpStream::connect(address).unwrap());
+            if !response.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(response.ends_with("\"hello\""), "{}", response);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_limit_connections_of_unix_socket_server() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+        use server::ServerConfig;
+
+        let path = ::std::env::temp_dir().join(format!("resty-limit-{}.sock", ::std::process::id()));
+        let mut router = Router::new();
+        router.get("/hello", |_: Request<_>| Ok("hello") as Result<_, Error>);
+        let config = ServerConfig::unix(&path).threads(2).max_connections(1);
+        let listening = router.bind_with(config).unwrap();
+        assert_eq!(listening.path(), Some(&*path));
+
+        let get = |stream: &mut UnixStream| -> String {
+            let mut response = vec![0u8; 1024];
+            let _ = stream.write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            let read = stream.read(&mut response).unwrap_or(0);
+            String::from_utf8_lossy(&response[..read]).into_owned()
+        };
+
+        let mut first = UnixStream::connect(&path).unwrap();
+        assert!(get(&mut first).ends_with("\"hello\""));
+        // Over the limit, the connection is closed.
+        assert_eq!(get(&mut UnixStream::connect(&path).unwrap()), "");
+    }
+
+    #[test]
+    fn should_keep_accepting_after_listener_errors() {
+        use std::io;
+        use std::sync::{Arc, Mutex};
+        use futures::{future, stream};
+        use tokio_core::net::TcpStream;
+        use tokio_core::reactor::Core;
+        use concurrency::Limiter;
+        use super::{Accept, AcceptErrorHook};
+
+        let mut core = Core::new().unwrap();
+        let incoming = stream::iter_result::<_, Accept<TcpStream>, _>(vec![
+            // EMFILE (too many open files).
+            Err(io::Error::from_raw_os_error(24)),
+            Ok(Box::new(future::err(io::ErrorKind::InvalidData.into())) as Accept<_>),
+        ]);
+        let errors = Arc::new(Mutex::new(vec![]));
+        let on_error = {
+            let errors = errors.clone();
+            AcceptErrorHook(Arc::new(move |err: &io::Error| errors.lock().unwrap().push(err.raw_os_error())))
+        };
+        let server = Router::new().into_service();
+        let http = hyper::server::Http::new();
+        let serve = server.serve(&core.handle(), incoming, &http, &Limiter::new(None, Default::default()), Some(&on_error));
+
+        assert!(core.run(serve).is_ok());
+        assert_eq!(*errors.lock().unwrap(), vec![Some(24)]);
+    }
+
+    #[test]
+    fn should_serve_on_external_event_loop() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::thread;
+        use futures::sync::oneshot;
+        use tokio_core::net::TcpListener;
+        use tokio_core::reactor::Core;
+
+        let mut router = Router::new();
+        router.get("/hello", |_: Request<_>| Ok("hello") as Result<_, Error>);
+
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+        let address = listener.local_addr().unwrap();
+        handle.spawn(router.serve_on(&handle, listener).map_err(|_| ()));
+
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            tx.send(response).unwrap();
+        });
+
+        let response = core.run(rx).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\"hello\""), "{}", response);
     }
 }
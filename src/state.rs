@@ -0,0 +1,75 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// A chain of shared states, ordered from the innermost router.
+#[derive(Clone, Default)]
+pub struct States {
+    states: Arc<Vec<Arc<Any + Send + Sync>>>,
+}
+
+impl fmt::Debug for States {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "States({})", self.states.len())
+    }
+}
+
+impl States {
+    /// Adds a state with the lowest priority.
+    pub fn push(&mut self, state: Arc<Any + Send + Sync>) {
+        Arc::make_mut(&mut self.states).push(state);
+    }
+
+    /// Adds all states from `other` with lower priority than existing ones.
+    pub fn extend(&mut self, other: &States) {
+        Arc::make_mut(&mut self.states).extend(other.states.iter().cloned());
+    }
+
+    /// Returns the nearest state of given type.
+    pub fn get<S: Send + Sync + 'static>(&self) -> Option<Arc<S>> {
+        self.states.iter()
+            .find(|state| state.is::<S>())
+            .and_then(|state| state.clone().downcast::<S>().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use error::Error;
+    use extract::State;
+    use router::Router;
+    use testing::TestClient;
+    use super::States;
+
+    #[test]
+    fn should_return_nearest_state() {
+        let mut inner = States::default();
+        inner.push(Arc::new(5u64));
+        let mut outer = States::default();
+        outer.push(Arc::new(1u64));
+        outer.push(Arc::new("outer"));
+        inner.extend(&outer);
+
+        assert_eq!(inner.get::<u64>(), Some(Arc::new(5)));
+        assert_eq!(inner.get::<&str>(), Some(Arc::new("outer")));
+        assert_eq!(inner.get::<u32>(), None);
+    }
+
+    #[test]
+    fn should_pass_nearest_state_to_nested_handlers() {
+        let mut products = Router::new().with_state(2u64);
+        products.get("/", |(State(id), State(name)): (State<u64>, State<String>)| {
+            Ok(format!("{} {}", name, id)) as Result<_, Error>
+        });
+        let mut router = Router::new().with_state(1u64);
+        router.get("/", |State(id): State<u64>| Ok(*id) as Result<_, Error>);
+        router.add("/products", products);
+        // States added after composition are visible to nested handlers too.
+        let client = TestClient::new(router.with_state("parent".to_owned()));
+
+        assert_eq!(client.get("/").send().text(), "1");
+        assert_eq!(client.get("/products/").send().text(), "\"parent 2\"");
+    }
+}
@@ -0,0 +1,354 @@
+//! Static files serving (see `Router::static_files`).
+
+use std::cmp;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::{future, Async, Future, Poll, Sink, Stream};
+use futures_cpupool::{Builder, CpuPool};
+use hyper::{self, header};
+use mime_guess;
+use percent_encoding::percent_decode;
+
+use error::Error;
+use request::Request;
+use request::params::DynamicParams;
+use response::Response;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of threads performing blocking file system operations.
+const POOL_SIZE: usize = 4;
+
+/// Result of serving a file.
+pub(crate) type ServeResult = Box<Future<Item = Response, Error = Error>>;
+
+/// File found under the requested path.
+enum Lookup {
+    File(File, Box<fs::Metadata>, PathBuf),
+    /// Directory requested without a trailing slash.
+    Directory,
+}
+
+/// Serves files from a directory.
+#[derive(Debug, Clone)]
+pub(crate) struct StaticFiles {
+    root: PathBuf,
+    index: String,
+    pool: CpuPool,
+}
+
+impl StaticFiles {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        StaticFiles {
+            root: root.into(),
+            index: "index.html".into(),
+            pool: Builder::new().pool_size(POOL_SIZE).name_prefix("resty-static-files-").create(),
+        }
+    }
+
+    /// Serves the file under `path` parameter of the request.
+    ///
+    /// File system is accessed on a thread pool, so that the event loop is not blocked.
+    pub fn serve(&self, request: &Request<DynamicParams>) -> ServeResult {
+        let path = match request.params().get_str("path") {
+            Ok(path) => path.to_owned(),
+            Err(err) => return Box::new(future::err(err.into())),
+        };
+        let files = self.clone();
+        let lookup = self.pool.spawn_fn(move || Ok::<_, ()>(files.lookup(&path)));
+
+        let files = self.clone();
+        let uri = request.uri().clone();
+        let headers = request.headers().clone();
+        Box::new(lookup.then(move |lookup| match lookup {
+            Ok(Some(Lookup::File(file, metadata, path))) => files.respond(&headers, file, &metadata, &path),
+            Ok(Some(Lookup::Directory)) => {
+                let location = match uri.query() {
+                    Some(query) => format!("{}/?{}", uri.path(), query),
+                    None => format!("{}/", uri.path()),
+                };
+                Ok(Response::raw(hyper::Response::new()
+                    .with_status(hyper::StatusCode::MovedPermanently)
+                    .with_header(header::Location::new(location))))
+            },
+            _ => Err(Error::not_found("File not found.")),
+        }))
+    }
+
+    /// Finds the file to serve (blocking).
+    fn lookup(&self, path: &str) -> Option<Lookup> {
+        let resolved = self.resolve(path)?;
+        let (file, metadata) = open(&resolved).ok()?;
+        if metadata.is_dir() {
+            if !path.is_empty() && !path.ends_with('/') {
+                return Some(Lookup::Directory);
+            }
+            let resolved = resolved.join(&self.index);
+            let (file, metadata) = open(&resolved).ok()?;
+            return if metadata.is_file() { Some(Lookup::File(file, Box::new(metadata), resolved)) } else { None };
+        }
+        if metadata.is_file() { Some(Lookup::File(file, Box::new(metadata), resolved)) } else { None }
+    }
+
+    fn respond(&self, headers: &hyper::Headers, file: File, metadata: &fs::Metadata, path: &Path) -> Result<Response, Error> {
+        let len = metadata.len();
+        let mtime = metadata.modified().ok().and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        // HTTP dates have a precision of seconds.
+        let modified = mtime.map(|since_epoch| UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()));
+        let etag = entity_tag(len, mtime, metadata);
+
+        let mut response = hyper::Response::new()
+            .with_header(header::ETag(etag.clone()))
+            .with_header(header::AcceptRanges(vec![header::RangeUnit::Bytes]));
+        if let Some(modified) = modified {
+            response.headers_mut().set(header::LastModified(modified.into()));
+        }
+
+        if is_not_modified(headers, &etag, modified) {
+            return Ok(Response::raw(response.with_status(hyper::StatusCode::NotModified)));
+        }
+
+        let range = match headers.get::<header::Range>() {
+            Some(range) if is_range_fresh(headers, &etag, modified) => Some(range),
+            _ => None,
+        };
+        let (start, length) = match range {
+            Some(header::Range::Bytes(ranges)) if ranges.len() == 1 => match ranges[0].to_satisfiable_range(len) {
+                Some((start, end)) => {
+                    response.set_status(hyper::StatusCode::PartialContent);
+                    response.headers_mut().set(header::ContentRange(header::ContentRangeSpec::Bytes {
+                        range: Some((start, end)),
+                        instance_length: Some(len),
+                    }));
+                    (start, end - start + 1)
+                },
+                None => {
+                    return Ok(Response::raw(response
+                        .with_status(hyper::StatusCode::RangeNotSatisfiable)
+                        .with_header(header::ContentRange(header::ContentRangeSpec::Bytes {
+                            range: None,
+                            instance_length: Some(len),
+                        }))));
+                },
+            },
+            // Multiple ranges are not supported, the whole file is returned instead.
+            _ => (0, len),
+        };
+
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        Ok(Response::raw(response
+            .with_header(header::ContentType(mime))
+            .with_header(header::ContentLength(length))
+            .with_body(self.stream(file, start, length))))
+    }
+
+    /// Streams `length` bytes of the file starting at `start`.
+    ///
+    /// Chunks are read on the thread pool, waiting for a slow client doesn't occupy any thread.
+    fn stream(&self, file: File, start: u64, length: u64) -> hyper::Body {
+        let (sender, body) = hyper::Body::pair();
+        let chunks = FileChunks { file, start: Some(start), remaining: length };
+        self.pool.spawn(chunks.forward(sender.sink_map_err(|_| ())).map(|_| ())).forget();
+        body
+    }
+
+    /// Maps the request path to a file within the root directory.
+    ///
+    /// Returns `None` for paths escaping the directory (`..` or symlinks pointing outside)
+    /// and for hidden files.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = percent_decode(path.as_bytes()).decode_utf8().ok()?;
+        let mut resolved = self.root.clone();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => return None,
+                segment if segment.contains(|c| c == '\\' || c == '\0') => return None,
+                // Hidden files and directories (i.e. `.git` or `.env`) are never served.
+                segment if segment.starts_with('.') => return None,
+                segment => {
+                    // Reject prefixes and root directories (i.e. `C:` on Windows).
+                    let mut components = Path::new(segment).components();
+                    match (components.next(), components.next()) {
+                        (Some(Component::Normal(_)), None) => resolved.push(segment),
+                        _ => return None,
+                    }
+                },
+            }
+        }
+
+        let root = self.root.canonicalize().ok()?;
+        let resolved = resolved.canonicalize().ok()?;
+        if resolved.starts_with(&root) {
+            Some(resolved)
+        } else {
+            None
+        }
+    }
+}
+
+fn open(path: &Path) -> io::Result<(File, fs::Metadata)> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    Ok((file, metadata))
+}
+
+/// Checks conditional request headers (`If-None-Match` takes precedence over `If-Modified-Since`).
+fn is_not_modified(headers: &hyper::Headers, etag: &header::EntityTag, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get::<header::IfNoneMatch>() {
+        return match *if_none_match {
+            header::IfNoneMatch::Any => true,
+            header::IfNoneMatch::Items(ref tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        };
+    }
+
+    match (headers.get::<header::IfModifiedSince>(), modified) {
+        (Some(since), Some(modified)) => modified <= SystemTime::from(since.0),
+        _ => false,
+    }
+}
+
+/// Checks whether `If-Range` allows serving the requested range (the whole file is served otherwise).
+fn is_range_fresh(headers: &hyper::Headers, etag: &header::EntityTag, modified: Option<SystemTime>) -> bool {
+    match headers.get::<header::IfRange>() {
+        None => true,
+        Some(header::IfRange::EntityTag(tag)) => tag.strong_eq(etag),
+        Some(header::IfRange::Date(date)) => modified == Some(SystemTime::from(*date)),
+    }
+}
+
+/// Builds a strong entity tag from file size, modification time and inode.
+fn entity_tag(len: u64, mtime: Option<Duration>, metadata: &fs::Metadata) -> header::EntityTag {
+    let mtime = mtime.unwrap_or_default();
+    header::EntityTag::strong(format!(
+        "{:x}-{:x}-{:x}.{:x}",
+        inode(metadata),
+        len,
+        mtime.as_secs(),
+        mtime.subsec_nanos(),
+    ))
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Reads chunks of the file (blocking).
+struct FileChunks {
+    file: File,
+    /// Position to seek to before the first read.
+    start: Option<u64>,
+    remaining: u64,
+}
+
+impl FileChunks {
+    fn read(&mut self) -> io::Result<hyper::Chunk> {
+        if let Some(start) = self.start.take() {
+            self.file.seek(SeekFrom::Start(start))?;
+        }
+
+        let mut buffer = vec![0; cmp::min(self.remaining, CHUNK_SIZE as u64) as usize];
+        match self.file.read(&mut buffer)? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buffer.truncate(read);
+                self.remaining -= read as u64;
+                Ok(buffer.into())
+            },
+        }
+    }
+}
+
+impl Stream for FileChunks {
+    type Item = Result<hyper::Chunk, hyper::Error>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(Async::Ready(None));
+        }
+
+        let chunk = self.read();
+        // The error is sent to the client and the stream ends.
+        if chunk.is_err() {
+            self.remaining = 0;
+        }
+        Ok(Async::Ready(Some(chunk.map_err(Into::into))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use hyper::StatusCode;
+
+    use router::Router;
+    use testing::TestClient;
+
+    #[test]
+    fn should_serve_static_files() {
+        let base = ::std::env::temp_dir().join(format!("resty-static-{}", ::std::process::id()));
+        let dir = base.join("public");
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        fs::write(dir.join("index.html"), "<h1>Hello</h1>").unwrap();
+        fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        fs::write(dir.join("css/main.css"), "body {}").unwrap();
+        fs::write(dir.join("large.bin"), vec![7u8; 3 * super::CHUNK_SIZE + 1]).unwrap();
+
+        let mut router = Router::new();
+        router.static_files("/static", &dir);
+        let client = TestClient::new(router);
+
+        let response = client.get("/static/css/main.css").send();
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.header("Content-Type"), Some("text/css"));
+        assert_eq!(response.text(), "body {}");
+        let etag = response.header("ETag").unwrap().to_owned();
+        assert!(response.header("Last-Modified").is_some());
+
+        let response = client.get("/static/").send();
+        assert_eq!(response.header("Content-Type"), Some("text/html"));
+        assert_eq!(response.text(), "<h1>Hello</h1>");
+
+        let response = client.get("/static/css/main.css").header("Range", "bytes=2-4").send();
+        assert_eq!(response.status(), StatusCode::PartialContent);
+        assert_eq!(response.header("Content-Range"), Some("bytes 2-4/7"));
+        assert_eq!(response.text(), "dy ");
+        let response = client.get("/static/css/main.css").header("Range", "bytes=10-").send();
+        assert_eq!(response.status(), StatusCode::RangeNotSatisfiable);
+
+        let response = client.get("/static/css/main.css").header("Range", "bytes=2-4").header("If-Range", &etag).send();
+        assert_eq!(response.status(), StatusCode::PartialContent);
+        assert_eq!(response.text(), "dy ");
+        let response = client.get("/static/css/main.css").header("Range", "bytes=2-4").header("If-Range", "\"stale\"").send();
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.text(), "body {}");
+
+        let response = client.get("/static/css?v=1").send();
+        assert_eq!(response.status(), StatusCode::MovedPermanently);
+        assert_eq!(response.header("Location"), Some("/static/css/?v=1"));
+
+        let response = client.get("/static/css/main.css").header("If-None-Match", &etag).send();
+        assert_eq!(response.status(), StatusCode::NotModified);
+        assert!(response.body().is_empty());
+
+        let response = client.get("/static/large.bin").send();
+        assert_eq!(response.body().len(), 3 * super::CHUNK_SIZE + 1);
+
+        for path in &["/static/../secret.txt", "/static/css/%2e%2e/%2E%2E/secret.txt", "/static/missing.js", "/static/.env", "/static/%2Eenv"] {
+            assert_eq!(client.get(path).send().status(), StatusCode::NotFound, "{}", path);
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
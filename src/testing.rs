@@ -0,0 +1,217 @@
+//! Testing utilities.
+//!
+//! `TestClient` sends requests directly to the router (without binding a socket).
+//!
+//! ```rust,ignore
+//! let client = TestClient::new(router);
+//! let response = client.post("/products").json(&product).send();
+//! assert_eq!(response.status(), StatusCode::Ok);
+//! assert_eq!(response.json::<Product>().unwrap(), product);
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use futures::{Future, Stream};
+use hyper::{self, header};
+use hyper::server::Service;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use tokio_core::reactor::Core;
+
+use router::Router;
+use server::Server;
+
+/// Client invoking router handlers in-process.
+///
+/// Requests are processed on an event loop owned by the client,
+/// so handlers relying on timers (i.e. timeouts) work as well.
+pub struct TestClient {
+    server: Server,
+    core: RefCell<Core>,
+}
+
+impl fmt::Debug for TestClient {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "TestClient")
+    }
+}
+
+impl TestClient {
+    /// Creates a client for given router.
+    pub fn new(router: Router) -> Self {
+        TestClient {
+            server: router.into_service(),
+            core: RefCell::new(Core::new().expect("Unable to create event loop.")),
+        }
+    }
+
+    /// Creates a request with given method and path (including query).
+    pub fn request(&self, method: hyper::Method, path: &str) -> RequestBuilder<'_> {
+        let uri = path.parse().unwrap_or_else(|err| panic!("Invalid path {:?}: {}", path, err));
+        RequestBuilder {
+            client: self,
+            request: hyper::Request::new(method, uri),
+        }
+    }
+
+    /// Creates GET request.
+    pub fn get(&self, path: &str) -> RequestBuilder<'_> {
+        self.request(hyper::Method::Get, path)
+    }
+
+    /// Creates POST request.
+    pub fn post(&self, path: &str) -> RequestBuilder<'_> {
+        self.request(hyper::Method::Post, path)
+    }
+
+    /// Creates PUT request.
+    pub fn put(&self, path: &str) -> RequestBuilder<'_> {
+        self.request(hyper::Method::Put, path)
+    }
+
+    /// Creates PATCH request.
+    pub fn patch(&self, path: &str) -> RequestBuilder<'_> {
+        self.request(hyper::Method::Patch, path)
+    }
+
+    /// Creates DELETE request.
+    pub fn delete(&self, path: &str) -> RequestBuilder<'_> {
+        self.request(hyper::Method::Delete, path)
+    }
+
+    /// Processes the request and reads the whole response.
+    pub fn send(&self, request: hyper::Request) -> TestResponse {
+        let response = self.server.call(request).and_then(|response| {
+            let status = response.status();
+            let headers = response.headers().clone();
+            response.body().concat2().map(move |body| TestResponse {
+                status,
+                headers,
+                body: body.to_vec(),
+            })
+        });
+        self.core.borrow_mut().run(response).expect("The server does not return errors; qed")
+    }
+}
+
+/// Request builder.
+#[derive(Debug)]
+pub struct RequestBuilder<'a> {
+    client: &'a TestClient,
+    request: hyper::Request,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Sets the header (replacing previous values).
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.request.headers_mut().set_raw(name.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Sets the typed header.
+    pub fn typed_header<H: header::Header>(mut self, header: H) -> Self {
+        self.request.headers_mut().set(header);
+        self
+    }
+
+    /// Sets the raw request body.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        let body = body.into();
+        self.request.headers_mut().set(header::ContentLength(body.len() as u64));
+        self.request.set_body(body);
+        self
+    }
+
+    /// Sets JSON request body.
+    pub fn json<T: Serialize>(self, body: &T) -> Self {
+        let body = serde_json::to_vec(body).expect("Unable to serialize request body.");
+        self.typed_header(header::ContentType::json()).body(body)
+    }
+
+    /// Sends the request.
+    pub fn send(self) -> TestResponse {
+        self.client.send(self.request)
+    }
+}
+
+/// Response returned by the router.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: hyper::StatusCode,
+    headers: hyper::Headers,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Returns response status.
+    pub fn status(&self) -> hyper::StatusCode {
+        self.status
+    }
+
+    /// Returns response headers.
+    pub fn headers(&self) -> &hyper::Headers {
+        &self.headers
+    }
+
+    /// Returns the value of given header (if present and valid UTF-8).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get_raw(name)
+            .and_then(|raw| raw.one())
+            .and_then(|value| ::std::str::from_utf8(value).ok())
+    }
+
+    /// Returns response body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns response body as text (invalid UTF-8 sequences are replaced).
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Deserializes JSON response body.
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use hyper::StatusCode;
+
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use super::TestClient;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Product {
+        id: u64,
+        name: String,
+    }
+
+    #[test]
+    fn should_send_requests_through_router() {
+        let mut router = Router::new();
+        router.post("/products", |request: Request<_>| request.json().map(|mut product: Product| {
+            product.id = 5;
+            product
+        }));
+        router.get("/echo", |request: Request<_>| {
+            Ok(request.headers().get_raw("X-Echo").is_some()) as Result<_, Error>
+        });
+        let client = TestClient::new(router);
+
+        let response = client.post("/products").json(&Product { id: 0, name: "Tea".into() }).send();
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        assert_eq!(response.json::<Product>().unwrap(), Product { id: 5, name: "Tea".into() });
+
+        assert_eq!(client.get("/echo").header("X-Echo", "1").send().text(), "true");
+        assert_eq!(client.post("/products").body("{").send().status(), StatusCode::BadRequest);
+        assert_eq!(client.get("/unknown").send().status(), StatusCode::NotFound);
+    }
+}
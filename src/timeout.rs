@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+use futures::{Async, Future, Poll};
+use tokio_timer::Delay;
+
+/// Converts duration to (fractional) number of seconds.
+pub(crate) fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+/// Limits the time given future can take.
+///
+/// Uses the timer of the tokio-core reactor the future is polled on.
+/// When polled outside of the reactor, the timeout never fires.
+pub fn timeout<F: Future>(future: F, duration: Duration) -> Timeout<F> {
+    Timeout {
+        future,
+        delay: Some(Delay::new(Instant::now() + duration)),
+    }
+}
+
+/// A future resolving to `None` when the inner future does not complete in time.
+///
+/// The inner future is dropped (cancelled) when the timeout fires.
+pub struct Timeout<F> {
+    future: F,
+    delay: Option<Delay>,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Item = Option<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(item) = self.future.poll()? {
+            return Ok(Async::Ready(Some(item)));
+        }
+
+        let elapsed = match self.delay.as_mut().map(|delay| delay.poll()) {
+            Some(Ok(Async::Ready(()))) => true,
+            Some(Ok(Async::NotReady)) | None => false,
+            // No timer available, just wait for the inner future.
+            Some(Err(_)) => {
+                self.delay = None;
+                false
+            },
+        };
+
+        if elapsed {
+            Ok(Async::Ready(None))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use futures::{future, Future};
+    use hyper;
+    use tokio_core::reactor::Core;
+    use tokio_timer::Delay;
+
+    use config::Config;
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use testing::TestClient;
+    use super::timeout;
+
+    #[test]
+    fn should_time_out_on_reactor() {
+        let mut core = Core::new().unwrap();
+        let slow = future::empty::<(), ()>();
+        assert_eq!(core.run(timeout(slow, Duration::from_millis(10))), Ok(None));
+        assert_eq!(core.run(timeout(future::ok::<_, ()>(5), Duration::from_millis(10))), Ok(Some(5)));
+    }
+
+    #[test]
+    fn should_wait_for_future_without_timer() {
+        assert_eq!(timeout(future::ok::<_, ()>(5), Duration::from_millis(0)).wait(), Ok(Some(5)));
+    }
+
+    #[test]
+    fn should_time_out_slow_handlers() {
+        let slow = |_: Request<_>| Delay::new(Instant::now() + Duration::from_millis(50)).then(|_| Ok("done") as Result<_, Error>);
+        let mut router = Router::with_config(Config::new().timeout(Duration::from_millis(10)));
+        router.get("/slow", slow);
+        router.get("/slow/override", slow).timeout(Duration::from_secs(1));
+        let client = TestClient::new(router);
+
+        assert_eq!(client.get("/slow").send().status(), hyper::StatusCode::GatewayTimeout);
+        assert_eq!(client.get("/slow/override").send().status(), hyper::StatusCode::Ok);
+    }
+}
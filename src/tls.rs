@@ -0,0 +1,380 @@
+//! HTTPS support.
+//!
+//! Use `Router::bind_tls` to serve the router over TLS.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::{Async, Future, Poll};
+use rustls::{self, Session};
+use rustls::internal::pemfile;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use request::Extensions;
+use server::Connection;
+use timeout::timeout;
+
+/// Default time limit of the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificate chain presented and verified during the TLS handshake.
+///
+/// Available in request extensions (see `Request::peer_certificate`)
+/// when client certificates are requested (see `TlsConfig::client_ca_pem`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    chain: Vec<Vec<u8>>,
+}
+
+impl PeerCertificate {
+    /// DER-encoded certificate of the peer.
+    pub fn der(&self) -> &[u8] {
+        &self.chain[0]
+    }
+
+    /// DER-encoded certificates chain (starting with the peer certificate).
+    pub fn chain(&self) -> &[Vec<u8>] {
+        &self.chain
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClientAuth {
+    None,
+    Optional,
+    Required,
+}
+
+/// TLS server configuration.
+#[derive(Clone)]
+pub struct TlsConfig {
+    certs: Vec<rustls::Certificate>,
+    key: rustls::PrivateKey,
+    protocols: Vec<Vec<u8>>,
+    client_roots: rustls::RootCertStore,
+    client_auth: ClientAuth,
+    handshake_timeout: Duration,
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let protocols = self.protocols.iter().map(|p| String::from_utf8_lossy(p)).collect::<Vec<_>>();
+        fmt.debug_struct("TlsConfig")
+            .field("certs", &self.certs.len())
+            .field("protocols", &protocols)
+            .field("client_auth", &self.client_auth)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    /// Creates a config from PEM-encoded certificate chain and private key (PKCS#8 or RSA).
+    ///
+    /// By default only `http/1.1` is advertised via ALPN, client certificates are not requested
+    /// and the handshake has to complete within 10 seconds.
+    pub fn from_pem(cert_chain: &[u8], key: &[u8]) -> io::Result<Self> {
+        let certs = pemfile::certs(&mut BufReader::new(cert_chain))
+            .map_err(|_| invalid_pem("Unable to parse certificate chain."))?;
+        if certs.is_empty() {
+            return Err(invalid_pem("No certificates found."));
+        }
+        let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(key)).unwrap_or_default();
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut BufReader::new(key)).unwrap_or_default();
+        }
+        if keys.is_empty() {
+            return Err(invalid_pem("No private key found."));
+        }
+
+        Ok(TlsConfig {
+            certs,
+            key: keys.remove(0),
+            protocols: vec![b"http/1.1".to_vec()],
+            client_roots: rustls::RootCertStore::empty(),
+            client_auth: ClientAuth::None,
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Creates a config from PEM files with certificate chain and private key.
+    pub fn from_pem_files<A: AsRef<Path>, B: AsRef<Path>>(cert_chain: A, key: B) -> io::Result<Self> {
+        Self::from_pem(&fs::read(cert_chain)?, &fs::read(key)?)
+    }
+
+    /// Protocols advertised via ALPN in order of preference.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.protocols = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+        self
+    }
+
+    /// Requires clients to present a certificate signed by one of given CAs (PEM).
+    pub fn client_ca_pem(mut self, ca: &[u8]) -> io::Result<Self> {
+        let (added, _) = self.client_roots.add_pem_file(&mut BufReader::new(ca))
+            .map_err(|_| invalid_pem("Unable to parse CA certificates."))?;
+        if added == 0 {
+            return Err(invalid_pem("No CA certificates found."));
+        }
+        if self.client_auth == ClientAuth::None {
+            self.client_auth = ClientAuth::Required;
+        }
+        Ok(self)
+    }
+
+    /// Requires clients to present a certificate signed by one of CAs from given PEM file.
+    pub fn client_ca_file<P: AsRef<Path>>(self, ca: P) -> io::Result<Self> {
+        self.client_ca_pem(&fs::read(ca)?)
+    }
+
+    /// Accept clients without a certificate (certificates that are presented are still verified).
+    pub fn client_auth_optional(mut self) -> Self {
+        self.client_auth = ClientAuth::Optional;
+        self
+    }
+
+    /// Closes connections that do not complete the handshake within given time.
+    pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    /// Creates acceptor performing TLS handshakes.
+    pub(crate) fn build(self) -> io::Result<Acceptor> {
+        let verifier = match self.client_auth {
+            ClientAuth::None => rustls::NoClientAuth::new(),
+            ClientAuth::Optional => rustls::AllowAnyAnonymousOrAuthenticatedClient::new(self.client_roots),
+            ClientAuth::Required => rustls::AllowAnyAuthenticatedClient::new(self.client_roots),
+        };
+        let mut config = rustls::ServerConfig::new(verifier);
+        config.set_single_cert(self.certs, self.key)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        config.set_protocols(&self.protocols);
+        Ok(Acceptor {
+            config: Arc::new(config),
+            handshake_timeout: self.handshake_timeout,
+        })
+    }
+}
+
+fn invalid_pem(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Performs TLS handshakes of accepted connections.
+#[derive(Clone)]
+pub(crate) struct Acceptor {
+    config: Arc<rustls::ServerConfig>,
+    handshake_timeout: Duration,
+}
+
+impl Acceptor {
+    /// Performs the TLS handshake over given connection.
+    ///
+    /// Fails with `TimedOut` if the handshake does not complete in time.
+    pub(crate) fn accept<S>(&self, io: S) -> Box<Future<Item = TlsStream<S>, Error = io::Error>> where
+        S: AsyncRead + AsyncWrite + 'static,
+    {
+        let handshake = Accept(Some(TlsStream {
+            io,
+            session: rustls::ServerSession::new(&self.config),
+            eof: false,
+            shutdown: false,
+        }));
+        Box::new(timeout(handshake, self.handshake_timeout).and_then(|stream| {
+            stream.ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out."))
+        }))
+    }
+}
+
+/// TLS handshake future.
+pub(crate) struct Accept<S>(Option<TlsStream<S>>);
+
+impl<S: AsyncRead + AsyncWrite> Future for Accept<S> {
+    type Item = TlsStream<S>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        {
+            let stream = self.0.as_mut().expect("Polled after completion.");
+            loop {
+                try_nb!(stream.write_tls());
+                if !stream.session.is_handshaking() {
+                    break;
+                }
+                match try_nb!(stream.session.read_tls(&mut stream.io)) {
+                    0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    _ => stream.process_packets()?,
+                }
+            }
+        }
+        Ok(Async::Ready(self.0.take().expect("Checked above; qed")))
+    }
+}
+
+/// Server side of TLS connection.
+pub(crate) struct TlsStream<S> {
+    io: S,
+    session: rustls::ServerSession,
+    eof: bool,
+    shutdown: bool,
+}
+
+impl<S: Read + Write> TlsStream<S> {
+    /// Writes all pending TLS records to the underlying connection.
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            self.session.write_tls(&mut self.io)?;
+        }
+        Ok(())
+    }
+
+    fn process_packets(&mut self) -> io::Result<()> {
+        self.session.process_new_packets().map_err(|err| {
+            // Try to send the alert to the peer.
+            let _ = self.write_tls();
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })
+    }
+}
+
+impl<S: Read + Write> Read for TlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.write_tls() {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                other => other?,
+            }
+            let read = self.session.read(buf)?;
+            if read > 0 || self.eof || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.session.read_tls(&mut self.io)? {
+                0 => self.eof = true,
+                _ => self.process_packets()?,
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> Write for TlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.session.write(buf)?;
+        match self.write_tls() {
+            // The data is buffered in the session.
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock && written > 0 => Ok(written),
+            Err(err) => Err(err),
+            Ok(()) if written == 0 => self.session.write(buf),
+            Ok(()) => Ok(written),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.write_tls()?;
+        self.io.flush()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for TlsStream<S> {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for TlsStream<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        if !self.shutdown {
+            self.session.send_close_notify();
+            self.shutdown = true;
+        }
+        try_nb!(self.write_tls());
+        self.io.shutdown()
+    }
+}
+
+impl<S: Connection> Connection for TlsStream<S> {
+    fn extensions(&self, extensions: &mut Extensions) {
+        self.io.extensions(extensions);
+        if let Some(chain) = self.session.get_peer_certificates() {
+            if !chain.is_empty() {
+                extensions.insert(PeerCertificate {
+                    chain: chain.into_iter().map(|cert| cert.0).collect(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+    use rustls::{self, Session};
+    use rustls::internal::pemfile;
+    use webpki;
+
+    use error::Error;
+    use request::Request;
+    use router::Router;
+    use super::TlsConfig;
+
+    // Self-signed CA with server and client certificates (valid for 100 years).
+    const CA: &[u8] = include_bytes!("../tests/certs/ca.pem");
+    const SERVER_CERT: &[u8] = include_bytes!("../tests/certs/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("../tests/certs/server.key");
+    const CLIENT_CERT: &[u8] = include_bytes!("../tests/certs/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("../tests/certs/client.key");
+
+    fn get(address: &SocketAddr, client_cert: bool) -> (Option<Vec<u8>>, String) {
+        let mut config = rustls::ClientConfig::new();
+        config.root_store.add_pem_file(&mut &*CA).unwrap();
+        config.set_protocols(&[b"http/1.1".to_vec()]);
+        if client_cert {
+            let certs = pemfile::certs(&mut &*CLIENT_CERT).unwrap();
+            let key = pemfile::pkcs8_private_keys(&mut &*CLIENT_KEY).unwrap().remove(0);
+            config.set_single_client_cert(certs, key);
+        }
+        let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        let session = rustls::ClientSession::new(&Arc::new(config), name);
+        let mut stream = rustls::StreamOwned::new(session, TcpStream::connect(address).unwrap());
+
+        stream.write_all(b"GET /cert HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = vec![];
+        // The connection might be closed without `close_notify`.
+        let _ = stream.read_to_end(&mut response);
+        let protocol = stream.sess.get_alpn_protocol().map(ToOwned::to_owned);
+        (protocol, String::from_utf8(response).unwrap())
+    }
+
+    #[test]
+    fn should_serve_https_with_client_certificates() {
+        let mut router = Router::new();
+        router.get("/cert", |request: Request<_>| Ok(request.peer_certificate().is_some()) as Result<_, Error>);
+        let tls = TlsConfig::from_pem(SERVER_CERT, SERVER_KEY).unwrap()
+            .client_ca_pem(CA).unwrap()
+            .client_auth_optional();
+        let listening = router.bind_tls("127.0.0.1:0", tls).unwrap();
+        let address = *listening.addr().unwrap();
+
+        let (protocol, response) = get(&address, true);
+        assert_eq!(protocol, Some(b"http/1.1".to_vec()));
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("true"), "{}", response);
+
+        let (_, response) = get(&address, false);
+        assert!(response.ends_with("false"), "{}", response);
+    }
+
+    #[test]
+    fn should_close_connections_not_completing_handshake() {
+        use std::time::Duration;
+
+        let tls = TlsConfig::from_pem(SERVER_CERT, SERVER_KEY).unwrap()
+            .handshake_timeout(Duration::from_millis(50));
+        let listening = Router::new().bind_tls("127.0.0.1:0", tls).unwrap();
+
+        let mut stream = TcpStream::connect(listening.addr().unwrap()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+    }
+}
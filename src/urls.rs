@@ -0,0 +1,280 @@
+//! Reverse routing.
+
+use std::fmt;
+use percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use error::Error;
+use openapi;
+
+/// Error while generating URL of a named route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// There is no route with given name.
+    UnknownRoute(String),
+    /// Value of the parameter was not provided.
+    MissingParam(String),
+    /// The route does not have such parameter.
+    UnknownParam(String),
+    /// Value of the parameter was provided more than once.
+    DuplicateParam(String),
+    /// The value is not valid for the parameter.
+    InvalidParam {
+        /// Parameter name
+        param: String,
+        /// Provided value
+        value: String,
+        /// Expected type
+        expected: String,
+    },
+    /// Params provided to `url_for_params` don't serialize to a flat map.
+    InvalidParams(String),
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UrlError::UnknownRoute(ref name) => write!(fmt, "Unknown route: {:?}", name),
+            UrlError::MissingParam(ref param) => write!(fmt, "Missing parameter: {:?}", param),
+            UrlError::UnknownParam(ref param) => write!(fmt, "Unknown parameter: {:?}", param),
+            UrlError::DuplicateParam(ref param) => write!(fmt, "Duplicate parameter: {:?}", param),
+            UrlError::InvalidParam { ref param, ref value, ref expected } =>
+                write!(fmt, "Invalid value {:?} of parameter {:?}, expected {}", value, param, expected),
+            UrlError::InvalidParams(ref error) => write!(fmt, "Invalid parameters: {}", error),
+        }
+    }
+}
+
+impl From<UrlError> for Error {
+    fn from(err: UrlError) -> Self {
+        Error::internal(
+            "Unable to generate URL. That's most likely a bug in the handler.",
+            err.to_string(),
+        )
+    }
+}
+
+/// Params of a route declared with `url!` (see `Urls::url_for_typed`).
+///
+/// Implemented by the params struct generated by `url!`, which also has a setter
+/// for every parameter (parameter types have to implement `Display`):
+///
+/// ```rust,ignore
+/// let product = url!(/v1/products/{id:u64});
+/// let link = product.parser.clone();
+/// router.get(product, handler).name("product");
+///
+/// assert_eq!(router.url_for_typed("product", &link.id(5)), Ok("/v1/products/5".into()));
+/// ```
+pub trait UrlParams {
+    /// Returns names and values of the params.
+    fn url_params(&self) -> Vec<(&'static str, String)>;
+}
+
+#[derive(Debug, Clone)]
+struct NamedRoute {
+    name: String,
+    pattern: String,
+    param_types: Vec<(String, String)>,
+}
+
+/// Patterns of named routes (see `Route::name`).
+///
+/// Available to handlers via `Request::url_for`.
+#[derive(Debug, Clone, Default)]
+pub struct Urls {
+    routes: Vec<NamedRoute>,
+}
+
+impl Urls {
+    pub(crate) fn add(&mut self, name: &str, pattern: String, param_types: &[(String, String)]) {
+        self.routes.push(NamedRoute {
+            name: name.into(),
+            pattern,
+            param_types: param_types.to_vec(),
+        });
+    }
+
+    /// Returns names used by routes with different patterns and the patterns.
+    pub(crate) fn duplicate_names(&self) -> Vec<(String, Vec<String>)> {
+        let mut duplicates: Vec<(String, Vec<String>)> = vec![];
+        for (i, route) in self.routes.iter().enumerate() {
+            if self.routes[..i].iter().any(|other| other.name == route.name) {
+                continue;
+            }
+            let mut patterns = vec![route.pattern.clone()];
+            for other in &self.routes[i + 1..] {
+                if other.name == route.name && !patterns.contains(&other.pattern) {
+                    patterns.push(other.pattern.clone());
+                }
+            }
+            if patterns.len() > 1 {
+                duplicates.push((route.name.clone(), patterns));
+            }
+        }
+        duplicates
+    }
+
+    /// Builds the path of given route (parameter values are percent-encoded).
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let route = self.routes.iter()
+            .find(|route| route.name == name)
+            .ok_or_else(|| UrlError::UnknownRoute(name.into()))?;
+
+        let names = openapi::pattern_params(&route.pattern);
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|p| p.0 == param.0) {
+                return Err(UrlError::DuplicateParam(param.0.into()));
+            }
+            if !names.iter().any(|name| name == param.0) {
+                return Err(UrlError::UnknownParam(param.0.into()));
+            }
+        }
+
+        let mut path = String::with_capacity(route.pattern.len());
+        for (i, segment) in route.pattern.split('/').enumerate() {
+            if i > 0 {
+                path.push('/');
+            }
+            if segment.len() < 2 || !segment.starts_with('{') || !segment.ends_with('}') {
+                path.push_str(segment);
+                continue;
+            }
+
+            let param = &segment[1..segment.len() - 1];
+            let catch_all = param.starts_with('*');
+            let param = param.trim_start_matches('*');
+            let value = params.iter()
+                .find(|p| p.0 == param)
+                .map(|p| p.1)
+                .ok_or_else(|| UrlError::MissingParam(param.into()))?;
+            let param_type = route.param_types.iter()
+                .find(|p| p.0 == param)
+                .map_or("String", |p| &*p.1);
+            if !is_valid(param_type, value, catch_all) {
+                return Err(UrlError::InvalidParam {
+                    param: param.into(),
+                    value: value.into(),
+                    expected: param_type.into(),
+                });
+            }
+            if catch_all {
+                let segments = value.split('/')
+                    .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT_ENCODE_SET).to_string())
+                    .collect::<Vec<_>>();
+                path.push_str(&segments.join("/"));
+            } else {
+                path.extend(utf8_percent_encode(value, PATH_SEGMENT_ENCODE_SET));
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Builds the path of given route with params generated by `url!` (see `UrlParams`).
+    pub fn url_for_typed<T: UrlParams>(&self, name: &str, params: &T) -> Result<String, UrlError> {
+        let values = params.url_params();
+        let params = values.iter().map(|(k, v)| (*k, &**v)).collect::<Vec<_>>();
+        self.url_for(name, &params)
+    }
+
+    /// Builds the path of given route with params taken from fields of given struct.
+    pub fn url_for_params<T: Serialize>(&self, name: &str, params: &T) -> Result<String, UrlError> {
+        let map = match serde_json::to_value(params) {
+            Ok(Value::Object(map)) => map,
+            Ok(other) => return Err(UrlError::InvalidParams(format!("Expected a struct, got: {}", other))),
+            Err(err) => return Err(UrlError::InvalidParams(err.to_string())),
+        };
+        let mut values = vec![];
+        for (key, value) in map {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                other => return Err(UrlError::InvalidParam {
+                    param: key,
+                    value: other.to_string(),
+                    expected: "a string, number or boolean".into(),
+                }),
+            };
+            values.push((key, value));
+        }
+        let params = values.iter().map(|(k, v)| (&**k, &**v)).collect::<Vec<_>>();
+        self.url_for(name, &params)
+    }
+}
+
+/// Checks if the value can be parsed by `url!` parser of given type.
+/// Only catch-all parameters may be empty.
+fn is_valid(param_type: &str, value: &str, catch_all: bool) -> bool {
+    if !catch_all && value.is_empty() {
+        return false;
+    }
+
+    macro_rules! parses {
+        ($($t:ident),*) => {
+            match param_type {
+                $(stringify!($t) => value.parse::<$t>().is_ok(),)*
+                _ => true,
+            }
+        };
+    }
+    parses!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, bool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UrlError, Urls};
+
+    #[test]
+    fn should_build_urls() {
+        let mut urls = Urls::default();
+        urls.add("tag", "/v1/products/{id}/tags/{tag}".into(), &[("id".into(), "u64".into())]);
+
+        assert_eq!(urls.url_for("tag", &[("tag", "new"), ("id", "5")]), Ok("/v1/products/5/tags/new".into()));
+        assert_eq!(urls.url_for("product", &[]), Err(UrlError::UnknownRoute("product".into())));
+        assert_eq!(urls.url_for("tag", &[("id", "5")]), Err(UrlError::MissingParam("tag".into())));
+        assert_eq!(
+            urls.url_for("tag", &[("id", "5"), ("tag", "a"), ("page", "1")]),
+            Err(UrlError::UnknownParam("page".into()))
+        );
+        assert_eq!(urls.url_for("tag", &[("id", "x"), ("tag", "a")]), Err(UrlError::InvalidParam {
+            param: "id".into(),
+            value: "x".into(),
+            expected: "u64".into(),
+        }));
+        assert_eq!(
+            urls.url_for("tag", &[("id", "5"), ("id", "6"), ("tag", "a")]),
+            Err(UrlError::DuplicateParam("id".into()))
+        );
+        assert!(urls.url_for("tag", &[("id", "5"), ("tag", "")]).is_err());
+    }
+
+    #[test]
+    fn should_percent_encode_params() {
+        let mut urls = Urls::default();
+        urls.add("tag", "/v1/products/{id}/tags/{tag}".into(), &[("id".into(), "u64".into())]);
+        urls.add("file", "/static/{*path}".into(), &[]);
+
+        assert_eq!(
+            urls.url_for("tag", &[("id", "5"), ("tag", "a/b c?#%")]),
+            Ok("/v1/products/5/tags/a%2Fb%20c%3F%23%25".into())
+        );
+        assert_eq!(urls.url_for("file", &[("path", "css/a b.css")]), Ok("/static/css/a%20b.css".into()));
+        assert_eq!(urls.url_for("file", &[("path", "")]), Ok("/static/".into()));
+    }
+
+    #[test]
+    fn should_report_duplicate_names() {
+        let mut urls = Urls::default();
+        urls.add("product", "/v1/products/{id}".into(), &[]);
+        urls.add("product", "/v1/products/{id}".into(), &[]);
+        assert!(urls.duplicate_names().is_empty());
+
+        urls.add("product", "/v2/products/{id}".into(), &[]);
+        assert_eq!(urls.duplicate_names(), vec![
+            ("product".to_owned(), vec!["/v1/products/{id}".to_owned(), "/v2/products/{id}".to_owned()]),
+        ]);
+    }
+}
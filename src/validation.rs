@@ -0,0 +1,252 @@
+//! Validation of deserialized request payloads.
+//!
+//! The `Validate` trait can be implemented manually or derived
+//! with `#[derive(Validate)]` from `resty_derive` crate.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::Regex;
+use serde_json;
+
+use error;
+
+/// A type that can be validated after deserialization.
+pub trait Validate {
+    /// Validates the value and returns a list of all invalid fields.
+    fn validate(&self) -> Result<(), Errors>;
+}
+
+/// A single invalid field.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// Path to the field (i.e. `items[0].name`)
+    pub path: String,
+    /// Reason why the value is invalid.
+    pub reason: String,
+}
+
+/// A list of validation errors.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Errors {
+    errors: Vec<FieldError>,
+}
+
+impl Errors {
+    /// Creates an empty list of errors.
+    pub fn new() -> Self {
+        Errors::default()
+    }
+
+    /// Adds an error for given field path.
+    pub fn add<A: Into<String>, B: Into<String>>(&mut self, path: A, reason: B) {
+        self.errors.push(FieldError {
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Adds all errors of a nested value, prefixing their paths with given `path`.
+    pub fn nested(&mut self, path: &str, errors: Errors) {
+        for error in errors.errors {
+            let nested_path = if error.path.is_empty() {
+                path.to_owned()
+            } else if error.path.starts_with('[') {
+                format!("{}{}", path, error.path)
+            } else {
+                format!("{}.{}", path, error.path)
+            };
+            self.errors.push(FieldError {
+                path: nested_path,
+                reason: error.reason,
+            });
+        }
+    }
+
+    /// Returns `true` if there are no errors.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns all collected errors.
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    /// Converts to `Ok(())` if there are no errors.
+    pub fn into_result(self) -> Result<(), Errors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for Errors {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(fmt, ", ")?;
+            }
+            write!(fmt, "{}: {}", error.path, error.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Errors> for error::Error {
+    fn from(errors: Errors) -> Self {
+        let details = format!("{}", errors);
+        let data = serde_json::to_value(&errors.errors).expect("The serialization is infallible; qed");
+        error::Error::unprocessable_entity(
+            "Request payload is invalid.",
+            details,
+        ).with_data(data)
+    }
+}
+
+/// A value that has a length.
+pub trait Length {
+    /// Returns the length of the value.
+    fn length(&self) -> usize;
+}
+
+impl Length for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl<T> Length for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A numeric value that can be range-checked.
+pub trait Number {
+    /// Returns the value as `f64`.
+    fn as_f64(&self) -> f64;
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl Number for $t {
+                fn as_f64(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    }
+}
+
+impl_number!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Validates that the length of the value is within given bounds (inclusive).
+pub fn length<T: Length + ?Sized>(value: &T, min: Option<usize>, max: Option<usize>) -> Result<(), String> {
+    let len = value.length();
+    match (min, max) {
+        (Some(min), _) if len < min => Err(format!("Length must be at least {}, got {}.", min, len)),
+        (_, Some(max)) if len > max => Err(format!("Length must be at most {}, got {}.", max, len)),
+        _ => Ok(()),
+    }
+}
+
+/// Validates that the value is within given range (inclusive).
+pub fn range<T: Number>(value: &T, min: Option<f64>, max: Option<f64>) -> Result<(), String> {
+    let val = value.as_f64();
+    match (min, max) {
+        (Some(min), _) if val < min => Err(format!("Value must be at least {}, got {}.", min, val)),
+        (_, Some(max)) if val > max => Err(format!("Value must be at most {}, got {}.", max, val)),
+        _ => Ok(()),
+    }
+}
+
+thread_local! {
+    static REGEX_CACHE: RefCell<HashMap<&'static str, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Validates that the value matches given regular expression.
+///
+/// Compiled expressions are cached, so the pattern is only compiled once (per thread).
+///
+/// # Panics
+///
+/// Panics if the pattern is not a valid regular expression.
+pub fn regex(value: &str, pattern: &'static str) -> Result<(), String> {
+    REGEX_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let regex = cache.entry(pattern).or_insert_with(|| {
+            Regex::new(pattern).unwrap_or_else(|e| panic!("Invalid validation pattern {:?}: {}", pattern, e))
+        });
+
+        if regex.is_match(value) {
+            Ok(())
+        } else {
+            Err(format!("Value does not match pattern {:?}.", pattern))
+        }
+    })
+}
+
+/// Validates that the value is an e-mail address.
+pub fn email(value: &str) -> Result<(), String> {
+    regex(value, r"^[^@\s]+@[^@\s.]+(\.[^@\s.]+)+$")
+        .map_err(|_| "Value is not a valid e-mail address.".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{length, range, regex, email, Errors};
+
+    #[test]
+    fn should_validate_length() {
+        assert_eq!(length("abc", Some(1), Some(3)), Ok(()));
+        assert_eq!(length("zażółć", None, Some(6)), Ok(()));
+        assert!(length("", Some(1), None).is_err());
+        assert!(length(&vec![1, 2, 3], None, Some(2)).is_err());
+    }
+
+    #[test]
+    fn should_validate_range() {
+        assert_eq!(range(&5u8, Some(0.0), Some(5.0)), Ok(()));
+        assert!(range(&-1i64, Some(0.0), None).is_err());
+        assert!(range(&5.5f64, None, Some(5.0)).is_err());
+    }
+
+    #[test]
+    fn should_validate_regex_and_email() {
+        assert_eq!(regex("abc", "^[a-z]+$"), Ok(()));
+        assert!(regex("ABC", "^[a-z]+$").is_err());
+        assert_eq!(email("resty@example.com"), Ok(()));
+        assert!(email("resty@example").is_err());
+        assert!(email("resty.example.com").is_err());
+    }
+
+    #[test]
+    fn should_prefix_nested_errors() {
+        let mut nested = Errors::new();
+        nested.add("name", "Too short.");
+        let mut list = Errors::new();
+        list.nested("[1]", nested);
+        let mut errors = Errors::new();
+        errors.add("id", "Too big.");
+        errors.nested("items", list);
+
+        assert_eq!(format!("{}", errors), "id: Too big., items[1].name: Too short.");
+    }
+}
@@ -0,0 +1,83 @@
+extern crate futures;
+extern crate hyper;
+extern crate resty;
+#[macro_use]
+extern crate resty_derive;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use futures::Future;
+use resty::validation::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+struct Item {
+    #[validate(length(min = 1))]
+    pub name: String,
+    #[validate(range(min = -10, max = 10.5))]
+    pub price: f64,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct Order {
+    #[validate(email)]
+    pub email: String,
+    #[validate(regex = "^[A-Z]{2}$", length(max = 2))]
+    pub country: Option<String>,
+    #[validate(length(max = 2), nested)]
+    pub items: Vec<Item>,
+    pub note: String,
+}
+
+fn request(body: &str) -> resty::Request {
+    let mut request = hyper::Request::new(hyper::Method::Post, "/".parse().unwrap());
+    request.set_body(body.to_owned());
+    resty::Request::new(request, ())
+}
+
+#[test]
+fn should_validate_derived_struct() {
+    let order = Order {
+        email: "resty@example.com".into(),
+        country: None,
+        items: vec![Item { name: "Bread".into(), price: 1.5 }],
+        note: "".into(),
+    };
+    assert!(order.validate().is_ok());
+
+    let order = Order {
+        email: "resty".into(),
+        country: Some("pl".into()),
+        items: vec![
+            Item { name: "Bread".into(), price: 1.5 },
+            Item { name: "".into(), price: 11.0 },
+            Item { name: "Butter".into(), price: -11.0 },
+        ],
+        note: "".into(),
+    };
+    let errors = order.validate().unwrap_err();
+    let paths = errors.errors().iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(paths, vec!["email", "country", "items", "items[1].name", "items[1].price", "items[2].price"]);
+}
+
+#[test]
+fn should_return_unprocessable_entity_for_invalid_json() {
+    let result = request(r#"{"email":"resty","items":[{"name":"","price":1}],"note":""}"#)
+        .valid_json::<Order>()
+        .wait();
+    let error: resty::Error = result.unwrap_err().into();
+
+    assert_eq!(error.code, resty::StatusCode::UnprocessableEntity);
+    assert_eq!(error.data, Some(serde_json::json!([
+        { "path": "email", "reason": "Value is not a valid e-mail address." },
+        { "path": "items[0].name", "reason": "Length must be at least 1, got 0." },
+    ])));
+
+    // Fields without rules are not validated.
+    let order = request(r#"{"email":"resty@example.com","items":[],"note":""}"#)
+        .valid_json::<Order>()
+        .wait()
+        .unwrap();
+    assert_eq!(order.email, "resty@example.com");
+    assert_eq!(order.note, "");
+}